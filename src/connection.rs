@@ -0,0 +1,209 @@
+use crate::request::{Request, RequestParseError};
+use crate::response::Response;
+use std::error::Error;
+use std::io::{self, Read, Write};
+
+/// A connection with a client, which buffers received bytes so that requests pipelined
+/// on the connection are read one by one.
+pub struct Connection<S> {
+    stream: S,
+    buffer: Vec<u8>,
+    max_head_size: usize,
+    max_body_size: usize,
+}
+
+impl<S: Read> Connection<S> {
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            buffer: Vec::new(),
+            max_head_size: 8 * 1024,
+            max_body_size: 1024 * 1024,
+        }
+    }
+
+    /// Set the maximum size of the request line and headers in bytes.
+    pub fn max_head_size(self, max_head_size: usize) -> Self {
+        Self {
+            max_head_size,
+            ..self
+        }
+    }
+
+    /// Set the maximum size of the request body in bytes.
+    pub fn max_body_size(self, max_body_size: usize) -> Self {
+        Self {
+            max_body_size,
+            ..self
+        }
+    }
+
+    /// Return the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Check if bytes of the next request are already received.
+    pub fn has_buffered(&self) -> bool {
+        !self.buffer.is_empty()
+    }
+
+    /// Read the next request on the connection.
+    /// Return `None` if the connection is closed or times out before a new request arrives.
+    pub fn read_request(&mut self) -> Result<Option<Request>, Box<dyn Error>> {
+        let head_length = loop {
+            if let Some(head_length) = Request::head_length(&self.buffer) {
+                break head_length;
+            }
+            if self.buffer.len() > self.max_head_size {
+                return Err(Box::new(RequestParseError::HeadTooLarge));
+            }
+            match self.fill_buffer() {
+                Ok(0) if self.buffer.is_empty() => return Ok(None),
+                Ok(0) => return Err(Box::new(RequestParseError::IncompleteHead)),
+                Ok(_) => continue,
+                Err(err) if self.buffer.is_empty() && is_timeout(&err) => return Ok(None),
+                Err(err) => return Err(Box::new(err)),
+            }
+        };
+        if head_length > self.max_head_size {
+            return Err(Box::new(RequestParseError::HeadTooLarge));
+        }
+        let head = std::str::from_utf8(&self.buffer[..head_length])?;
+        let mut request = Request::parse_head(head)?;
+
+        let content_length = request.content_length()?;
+        if content_length > self.max_body_size {
+            return Err(Box::new(RequestParseError::BodyTooLarge));
+        }
+        let request_length = head_length + content_length;
+        while self.buffer.len() < request_length {
+            if self.fill_buffer()? == 0 {
+                return Err(Box::new(RequestParseError::IncompleteBody));
+            }
+        }
+        request.body = self.buffer[head_length..request_length].to_vec();
+        // Bytes after the request belong to the next pipelined request.
+        self.buffer.drain(..request_length);
+        Ok(Some(request))
+    }
+
+    /// Read bytes from the stream into the buffer and return how many bytes are read.
+    fn fill_buffer(&mut self) -> io::Result<usize> {
+        let mut chunk = [0; 4096];
+        let length = self.stream.read(&mut chunk)?;
+        self.buffer.extend_from_slice(&chunk[..length]);
+        Ok(length)
+    }
+}
+
+impl<S: Write> Connection<S> {
+    pub fn write_response(&mut self, response: Response) -> io::Result<()> {
+        response.write_to(&mut self.stream)
+    }
+}
+
+/// Check if the error is caused by the read timeout of the socket.
+pub(crate) fn is_timeout(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::Connection;
+    use crate::request::RequestParseError;
+    use std::io::Read;
+
+    #[test]
+    fn test_read_pipelined_requests() {
+        let requests: &[u8] =
+            b"POST /a HTTP/1.1\r\nContent-Length: 3\r\n\r\nabcGET /b HTTP/1.1\r\n\r\n";
+        let mut connection = Connection::new(requests);
+        let request = connection.read_request().unwrap().unwrap();
+        assert_eq!(request.uri, "/a");
+        assert_eq!(request.body, b"abc".to_vec());
+        let request = connection.read_request().unwrap().unwrap();
+        assert_eq!(request.uri, "/b");
+        assert!(connection.read_request().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_read_chunked_request() {
+        // The chunked body must not be read as the next request.
+        let requests: &[u8] = b"POST /a HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+            1a\r\nGET /smuggled HTTP/1.1\r\n\r\n\r\n0\r\n\r\nGET /b HTTP/1.1\r\n\r\n";
+        let mut connection = Connection::new(requests);
+        let err = connection.read_request().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RequestParseError>(),
+            Some(RequestParseError::UnsupportedTransferEncoding)
+        ));
+    }
+
+    #[test]
+    fn test_read_incomplete_body() {
+        let requests: &[u8] = b"POST /a HTTP/1.1\r\nContent-Length: 10\r\n\r\nabc";
+        let mut connection = Connection::new(requests);
+        let err = connection.read_request().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RequestParseError>(),
+            Some(RequestParseError::IncompleteBody)
+        ));
+    }
+
+    #[test]
+    fn test_read_incomplete_head() {
+        let requests: &[u8] = b"GET / HTTP/1.1\r\nHost: local";
+        let mut connection = Connection::new(requests);
+        let err = connection.read_request().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RequestParseError>(),
+            Some(RequestParseError::IncompleteHead)
+        ));
+    }
+
+    #[test]
+    fn test_read_too_large_head() {
+        let mut requests = b"GET / HTTP/1.1\r\n".to_vec();
+        for _ in 0..100 {
+            requests.extend_from_slice(b"User-Agent: curl/7.58.0\r\n");
+        }
+        requests.extend_from_slice(b"\r\n");
+
+        let mut connection = Connection::new(&requests[..]).max_head_size(1024);
+        let err = connection.read_request().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RequestParseError>(),
+            Some(RequestParseError::HeadTooLarge)
+        ));
+
+        let mut connection = Connection::new(&requests[..]).max_head_size(4096);
+        assert!(connection.read_request().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_read_too_large_body() {
+        let requests: &[u8] = b"POST /a HTTP/1.1\r\nContent-Length: 11\r\n\r\nhello world";
+        let mut connection = Connection::new(requests).max_body_size(10);
+        let err = connection.read_request().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RequestParseError>(),
+            Some(RequestParseError::BodyTooLarge)
+        ));
+
+        let mut connection = Connection::new(requests).max_body_size(11);
+        assert!(connection.read_request().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_read_request_split_into_chunks() {
+        // `Read` for `Chain` returns bytes of the first reader and then the second one.
+        let requests = (&b"GET / HTTP/1.1\r\nHo"[..]).chain(&b"st: localhost\r\n\r\n"[..]);
+        let mut connection = Connection::new(requests);
+        let request = connection.read_request().unwrap().unwrap();
+        assert_eq!(request.uri, "/");
+    }
+}
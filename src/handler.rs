@@ -1,13 +1,14 @@
-use crate::response::Response;
 use crate::request::Request;
+use crate::response::Response;
 
-pub trait Handler: 'static {
+/// Handlers are shared by worker threads, so they must be `Send` and `Sync`.
+pub trait Handler: Send + Sync + 'static {
     fn handle(&self, request: &Request) -> Response;
 }
 
-impl<F: 'static> Handler for F
+impl<F> Handler for F
 where
-    F: Fn(&Request) -> Response,
+    F: Fn(&Request) -> Response + Send + Sync + 'static,
 {
     fn handle(&self, request: &Request) -> Response {
         self(request)
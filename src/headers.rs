@@ -1,57 +1,283 @@
-use crate::request::RequestParseError;
-use std::collections::HashMap;
+use crate::request::{is_token, RequestParseError};
+use std::borrow::Cow;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::ops::Index;
 use std::str::FromStr;
-pub type HeaderMap = HashMap<HeaderField, String>;
-
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
-pub enum HeaderField {
-    // Request headers:
-    Accept,
-    Host,
-    UserAgent,
-    // Entity headers
-    ContentLength,
-    ContentType,
-    // Unrecognized header field
-    Undefined,
+
+/// Name of a header field. Names are compared case-insensitively, and the case of
+/// unregistered names is kept as received.
+#[derive(Clone, Debug)]
+pub struct HeaderName(Cow<'static, str>);
+
+macro_rules! header_names {
+    ($($constant:ident => $name:expr,)*) => {
+        impl HeaderName {
+            $(pub const $constant: HeaderName = HeaderName(Cow::Borrowed($name));)*
+        }
+
+        /// Names with constants, used to normalize the case of received names.
+        const STANDARD_NAMES: &[HeaderName] = &[$(HeaderName::$constant,)*];
+    };
+}
+
+header_names! {
+    ACCEPT => "Accept",
+    ACCEPT_CHARSET => "Accept-Charset",
+    ACCEPT_ENCODING => "Accept-Encoding",
+    ACCEPT_LANGUAGE => "Accept-Language",
+    ACCEPT_RANGES => "Accept-Ranges",
+    ACCESS_CONTROL_ALLOW_CREDENTIALS => "Access-Control-Allow-Credentials",
+    ACCESS_CONTROL_ALLOW_HEADERS => "Access-Control-Allow-Headers",
+    ACCESS_CONTROL_ALLOW_METHODS => "Access-Control-Allow-Methods",
+    ACCESS_CONTROL_ALLOW_ORIGIN => "Access-Control-Allow-Origin",
+    ACCESS_CONTROL_EXPOSE_HEADERS => "Access-Control-Expose-Headers",
+    ACCESS_CONTROL_MAX_AGE => "Access-Control-Max-Age",
+    ACCESS_CONTROL_REQUEST_HEADERS => "Access-Control-Request-Headers",
+    ACCESS_CONTROL_REQUEST_METHOD => "Access-Control-Request-Method",
+    AGE => "Age",
+    ALLOW => "Allow",
+    ALT_SVC => "Alt-Svc",
+    AUTHORIZATION => "Authorization",
+    CACHE_CONTROL => "Cache-Control",
+    CONNECTION => "Connection",
+    CONTENT_DISPOSITION => "Content-Disposition",
+    CONTENT_ENCODING => "Content-Encoding",
+    CONTENT_LANGUAGE => "Content-Language",
+    CONTENT_LENGTH => "Content-Length",
+    CONTENT_LOCATION => "Content-Location",
+    CONTENT_RANGE => "Content-Range",
+    CONTENT_SECURITY_POLICY => "Content-Security-Policy",
+    CONTENT_TYPE => "Content-Type",
+    COOKIE => "Cookie",
+    DATE => "Date",
+    ETAG => "ETag",
+    EXPECT => "Expect",
+    EXPIRES => "Expires",
+    FORWARDED => "Forwarded",
+    FROM => "From",
+    HOST => "Host",
+    IF_MATCH => "If-Match",
+    IF_MODIFIED_SINCE => "If-Modified-Since",
+    IF_NONE_MATCH => "If-None-Match",
+    IF_RANGE => "If-Range",
+    IF_UNMODIFIED_SINCE => "If-Unmodified-Since",
+    KEEP_ALIVE => "Keep-Alive",
+    LAST_MODIFIED => "Last-Modified",
+    LINK => "Link",
+    LOCATION => "Location",
+    MAX_FORWARDS => "Max-Forwards",
+    ORIGIN => "Origin",
+    PRAGMA => "Pragma",
+    PROXY_AUTHENTICATE => "Proxy-Authenticate",
+    PROXY_AUTHORIZATION => "Proxy-Authorization",
+    RANGE => "Range",
+    REFERER => "Referer",
+    REFERRER_POLICY => "Referrer-Policy",
+    RETRY_AFTER => "Retry-After",
+    SEC_WEBSOCKET_ACCEPT => "Sec-WebSocket-Accept",
+    SEC_WEBSOCKET_EXTENSIONS => "Sec-WebSocket-Extensions",
+    SEC_WEBSOCKET_KEY => "Sec-WebSocket-Key",
+    SEC_WEBSOCKET_PROTOCOL => "Sec-WebSocket-Protocol",
+    SEC_WEBSOCKET_VERSION => "Sec-WebSocket-Version",
+    SERVER => "Server",
+    SET_COOKIE => "Set-Cookie",
+    STRICT_TRANSPORT_SECURITY => "Strict-Transport-Security",
+    TE => "TE",
+    TRAILER => "Trailer",
+    TRANSFER_ENCODING => "Transfer-Encoding",
+    UPGRADE => "Upgrade",
+    USER_AGENT => "User-Agent",
+    VARY => "Vary",
+    VIA => "Via",
+    WARNING => "Warning",
+    WWW_AUTHENTICATE => "WWW-Authenticate",
+    X_CONTENT_TYPE_OPTIONS => "X-Content-Type-Options",
+    X_FORWARDED_FOR => "X-Forwarded-For",
+    X_FRAME_OPTIONS => "X-Frame-Options",
+}
+
+impl HeaderName {
+    /// Create a header name from a string literal.
+    /// Panics if the name is not a valid token.
+    pub fn from_static(name: &'static str) -> Self {
+        assert!(is_token(name), "Invalid header name: {:?}", name);
+        Self::standard(name).unwrap_or(HeaderName(Cow::Borrowed(name)))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn standard(name: &str) -> Option<Self> {
+        STANDARD_NAMES
+            .iter()
+            .find(|standard| standard.as_str().eq_ignore_ascii_case(name))
+            .cloned()
+    }
 }
 
-impl FromStr for HeaderField {
+impl PartialEq for HeaderName {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str().eq_ignore_ascii_case(other.as_str())
+    }
+}
+
+impl Eq for HeaderName {}
+
+// Must be consistent with case-insensitive `eq`.
+impl Hash for HeaderName {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for byte in self.as_str().bytes() {
+            state.write_u8(byte.to_ascii_lowercase());
+        }
+        state.write_u8(0xff);
+    }
+}
+
+impl FromStr for HeaderName {
     type Err = RequestParseError;
+    /// Parse a header name. Names with constants are normalized to their case.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let header = match s {
-            "Accept" => HeaderField::Accept,
-            "Host" => HeaderField::Host,
-            "User-Agent" => HeaderField::UserAgent,
-            "Content-Length" => HeaderField::ContentLength,
-            "Content-Type" => HeaderField::ContentType,
-            _ => HeaderField::Undefined,
-        };
-        Ok(header)
+        if !is_token(s) {
+            return Err(RequestParseError::InvalidHeaderFormat);
+        }
+        Ok(Self::standard(s).unwrap_or_else(|| HeaderName(Cow::Owned(s.to_string()))))
     }
 }
 
-impl Into<Vec<u8>> for &HeaderField {
-    fn into(self) -> Vec<u8> {
-        let header_field = match self {
-            HeaderField::Accept => "Accept",
-            HeaderField::Host => "Host",
-            HeaderField::UserAgent => "User-Agent",
-            HeaderField::ContentLength => "Content-Length",
-            HeaderField::ContentType => "Content-Type",
-            HeaderField::Undefined => "",
-        };
-        header_field.as_bytes().to_vec()
+impl fmt::Display for HeaderName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl From<&HeaderName> for Vec<u8> {
+    fn from(header_name: &HeaderName) -> Self {
+        header_name.as_str().as_bytes().to_vec()
+    }
+}
+
+/// Map of header names and values which keeps insertion order.
+/// A name can have several values, such as `Set-Cookie` sent in separate lines.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct HeaderMap {
+    entries: Vec<(HeaderName, String)>,
+}
+
+impl HeaderMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the number of values, counting each value of the same name.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains_key(&self, name: &HeaderName) -> bool {
+        self.entries.iter().any(|(key, _)| key == name)
+    }
+
+    /// Return the first value of the name.
+    pub fn get(&self, name: &HeaderName) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Return all the values of the name in the order they were added.
+    pub fn get_all<'a>(&'a self, name: &'a HeaderName) -> impl Iterator<Item = &'a str> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Return the elements of a comma-separated list header such as `Vary` or `Via`.
+    /// Elements of all the values of the name are returned, without empty ones.
+    /// This must not be used for headers whose values can contain commas, such as
+    /// `Set-Cookie` and `Date`.
+    pub fn get_list<'a>(&'a self, name: &'a HeaderName) -> impl Iterator<Item = &'a str> + 'a {
+        self.get_all(name)
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|element| !element.is_empty())
+    }
+
+    /// Set the value of the name, replacing all the existing values. The value takes the
+    /// position of the first existing value. Return the first of the replaced values.
+    pub fn insert<V: Into<String>>(&mut self, name: HeaderName, value: V) -> Option<String> {
+        let value = value.into();
+        match self.entries.iter().position(|(key, _)| *key == name) {
+            Some(position) => {
+                let old = std::mem::replace(&mut self.entries[position].1, value);
+                let rest = self.entries.split_off(position + 1);
+                self.entries
+                    .extend(rest.into_iter().filter(|(key, _)| *key != name));
+                Some(old)
+            }
+            None => {
+                self.entries.push((name, value));
+                None
+            }
+        }
+    }
+
+    /// Add a value of the name after the existing ones.
+    pub fn append<V: Into<String>>(&mut self, name: HeaderName, value: V) {
+        self.entries.push((name, value.into()));
+    }
+
+    /// Remove all the values of the name and return the first of them.
+    pub fn remove(&mut self, name: &HeaderName) -> Option<String> {
+        let position = self.entries.iter().position(|(key, _)| key == name)?;
+        let (_, removed) = self.entries.remove(position);
+        self.entries.retain(|(key, _)| key != name);
+        Some(removed)
+    }
+
+    /// Iterate over names and values in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = (&HeaderName, &str)> {
+        self.entries
+            .iter()
+            .map(|(name, value)| (name, value.as_str()))
+    }
+}
+
+impl Index<&HeaderName> for HeaderMap {
+    type Output = str;
+
+    /// Return the first value of the name. Panics if the name is absent.
+    fn index(&self, name: &HeaderName) -> &str {
+        self.get(name)
+            .unwrap_or_else(|| panic!("No header named {}", name))
+    }
+}
+
+/// Values are appended in order, so repeated names keep all their values.
+impl FromIterator<(HeaderName, String)> for HeaderMap {
+    fn from_iter<I: IntoIterator<Item = (HeaderName, String)>>(iter: I) -> Self {
+        Self {
+            entries: iter.into_iter().collect(),
+        }
     }
 }
 
-/// Convert hash map of headers into vector of bytes joined by a newline character.
+/// Convert headers into vector of bytes joined by a newline character.
+/// Headers are written in the order they were added.
 // I tried to implement `Into<Vec<u8>>` for `Headers` but the implementation is reserved.
 pub fn to_vec(headers: &HeaderMap) -> Vec<u8> {
     let mut headers_vec = Vec::new();
-    for (header_field, header_value) in headers {
-        let mut header_field: Vec<u8> = header_field.into();
-        headers_vec.append(&mut header_field);
+    for (header_name, header_value) in headers.iter() {
+        let mut header_name: Vec<u8> = header_name.into();
+        headers_vec.append(&mut header_name);
         headers_vec.append(&mut ": ".as_bytes().to_vec());
         headers_vec.append(&mut header_value.as_bytes().to_vec());
         headers_vec.append(&mut "\r\n".as_bytes().to_vec());
@@ -61,18 +287,150 @@ pub fn to_vec(headers: &HeaderMap) -> Vec<u8> {
 
 #[cfg(test)]
 mod tests {
-    use crate::headers::{to_vec, HeaderField, HeaderMap};
+    use crate::headers::{to_vec, HeaderMap, HeaderName};
+    use crate::range::RangeSpec;
     use crate::request::Request;
+    use crate::typed_headers::{
+        Accept, Authorization, CacheControl, ContentLength, ContentType, Date, Host, MediaRange,
+        Range,
+    };
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    use std::str::FromStr;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn hash(header_name: &HeaderName) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        header_name.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_header_name_case_insensitive() {
+        let lower = HeaderName::from_str("content-length").unwrap();
+        assert_eq!(lower, HeaderName::CONTENT_LENGTH);
+        assert_eq!(hash(&lower), hash(&HeaderName::CONTENT_LENGTH));
+        // Names with constants are normalized.
+        assert_eq!(lower.as_str(), "Content-Length");
+
+        let custom = HeaderName::from_str("x-request-id").unwrap();
+        assert_eq!(custom.as_str(), "x-request-id");
+        assert_eq!(custom, HeaderName::from_static("X-Request-Id"));
+        assert_eq!(
+            hash(&custom),
+            hash(&HeaderName::from_static("X-REQUEST-ID"))
+        );
+        assert_ne!(custom, HeaderName::from_static("X-Request"));
+    }
+
+    #[test]
+    fn test_header_name_invalid() {
+        for name in &["", "Content Length", "Host:", "X-(Id)", "é"] {
+            assert!(HeaderName::from_str(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_header_name_from_static_invalid() {
+        HeaderName::from_static("X Request");
+    }
+
+    #[test]
+    fn test_header_map() {
+        let mut headers = HeaderMap::new();
+        assert!(headers.is_empty());
+        headers.append(HeaderName::SET_COOKIE, "a=1");
+        headers.append(HeaderName::HOST, "localhost");
+        headers.append(HeaderName::from_static("set-cookie"), "b=2");
+        assert_eq!(headers.len(), 3);
+        assert_eq!(headers.get(&HeaderName::SET_COOKIE), Some("a=1"));
+        assert_eq!(
+            headers
+                .get_all(&HeaderName::SET_COOKIE)
+                .collect::<Vec<&str>>(),
+            vec!["a=1", "b=2"]
+        );
+        assert_eq!(headers.get(&HeaderName::ACCEPT), None);
+        assert_eq!(headers.get_all(&HeaderName::ACCEPT).count(), 0);
+        assert!(headers.contains_key(&HeaderName::HOST));
+        assert_eq!(&headers[&HeaderName::HOST], "localhost");
+    }
+
+    #[test]
+    fn test_header_map_insert() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(headers.insert(HeaderName::HOST, "a"), None);
+        headers.append(HeaderName::VIA, "1.0 fred");
+        headers.append(HeaderName::ACCEPT, "*/*");
+        headers.append(HeaderName::VIA, "1.1 p.example.net");
+        // All the values are replaced in the position of the first one.
+        assert_eq!(
+            headers.insert(HeaderName::VIA, "1.1 proxy"),
+            Some("1.0 fred".to_string())
+        );
+        let entries = headers.iter().collect::<Vec<(&HeaderName, &str)>>();
+        assert_eq!(
+            entries,
+            vec![
+                (&HeaderName::HOST, "a"),
+                (&HeaderName::VIA, "1.1 proxy"),
+                (&HeaderName::ACCEPT, "*/*"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_header_map_remove() {
+        let mut headers = HeaderMap::new();
+        headers.append(HeaderName::VARY, "Accept");
+        headers.append(HeaderName::HOST, "localhost");
+        headers.append(HeaderName::VARY, "Cookie");
+        assert_eq!(
+            headers.remove(&HeaderName::VARY),
+            Some("Accept".to_string())
+        );
+        assert_eq!(headers.remove(&HeaderName::VARY), None);
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers.get(&HeaderName::HOST), Some("localhost"));
+    }
+
+    #[test]
+    fn test_header_map_get_list() {
+        let mut headers = HeaderMap::new();
+        headers.append(HeaderName::VARY, "Accept-Encoding, Accept");
+        headers.append(HeaderName::VARY, " ,Cookie,");
+        assert_eq!(
+            headers.get_list(&HeaderName::VARY).collect::<Vec<&str>>(),
+            vec!["Accept-Encoding", "Accept", "Cookie"]
+        );
+    }
+
+    #[test]
+    fn test_to_vec_keeps_order() {
+        let headers = [
+            (HeaderName::SET_COOKIE, "a=1".to_string()),
+            (HeaderName::CONTENT_TYPE, "text/html".to_string()),
+            (HeaderName::SET_COOKIE, "b=2".to_string()),
+        ]
+        .iter()
+        .cloned()
+        .collect::<HeaderMap>();
+        assert_eq!(
+            String::from_utf8(to_vec(&headers)).unwrap(),
+            "Set-Cookie: a=1\r\nContent-Type: text/html\r\nSet-Cookie: b=2\r\n"
+        );
+    }
 
     #[test]
     fn test_to_vec() {
         let headers = [
-            (HeaderField::ContentLength, "3".to_string()),
-            (HeaderField::UserAgent, "curl/7.58.0".to_string()),
-            (HeaderField::Host, "localhost:8000".to_string()),
-            (HeaderField::Accept, "*/*".to_string()),
+            (HeaderName::CONTENT_LENGTH, "3".to_string()),
+            (HeaderName::USER_AGENT, "curl/7.58.0".to_string()),
+            (HeaderName::HOST, "localhost:8000".to_string()),
+            (HeaderName::ACCEPT, "*/*".to_string()),
             (
-                HeaderField::ContentType,
+                HeaderName::CONTENT_TYPE,
                 "application/x-www-form-urlencoded".to_string(),
             ),
         ]
@@ -86,5 +444,56 @@ mod tests {
         let expected = Request::parse_headers(&expected).unwrap();
 
         assert_eq!(headers, expected,);
+
+        // Typed headers survive encoding, serialization, parsing and decoding.
+        let content_type = ContentType::new("text/html")
+            .param("charset", "utf-8")
+            .param("title", "a \"b\"; c");
+        let accept = Accept(vec![
+            MediaRange::new("text/html", 1000),
+            MediaRange::new("application/*", 500),
+            MediaRange::new("*/*", 1),
+        ]);
+        let cache_control = CacheControl::new()
+            .directive("no-cache", None)
+            .directive("max-age", Some("60"))
+            .directive("private", Some("Set-Cookie, Vary"));
+        let date = Date(UNIX_EPOCH + Duration::from_secs(784_111_777));
+        let host = Host {
+            host: "[::1]".to_string(),
+            port: Some(8000),
+        };
+        let range = Range(vec![
+            RangeSpec::FromTo(0, 99),
+            RangeSpec::From(200),
+            RangeSpec::Suffix(50),
+        ]);
+
+        let mut headers = HeaderMap::new();
+        headers.typed_insert(&content_type).unwrap();
+        headers.typed_insert(&ContentLength(3)).unwrap();
+        headers.typed_insert(&accept).unwrap();
+        headers.typed_insert(&cache_control).unwrap();
+        headers
+            .typed_insert(&Authorization::bearer("abc.def"))
+            .unwrap();
+        headers.typed_insert(&date).unwrap();
+        headers.typed_insert(&host).unwrap();
+        headers.typed_insert(&range).unwrap();
+
+        let lines = String::from_utf8(to_vec(&headers)).unwrap();
+        let lines = lines.split("\r\n").collect::<Vec<&str>>();
+        let parsed = Request::parse_headers(&lines).unwrap();
+        assert_eq!(parsed.typed_get::<ContentType>(), Ok(content_type));
+        assert_eq!(parsed.typed_get::<ContentLength>(), Ok(ContentLength(3)));
+        assert_eq!(parsed.typed_get::<Accept>(), Ok(accept));
+        assert_eq!(parsed.typed_get::<CacheControl>(), Ok(cache_control));
+        assert_eq!(
+            parsed.typed_get::<Authorization>(),
+            Ok(Authorization::bearer("abc.def"))
+        );
+        assert_eq!(parsed.typed_get::<Date>(), Ok(date));
+        assert_eq!(parsed.typed_get::<Host>(), Ok(host));
+        assert_eq!(parsed.typed_get::<Range>(), Ok(range));
     }
 }
@@ -0,0 +1,224 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DAY_NAMES: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const LONG_DAY_NAMES: [&str; 7] = [
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+];
+const MONTH_NAMES: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Format the time as an IMF-fixdate defined in RFC 7231, such as
+/// `Sun, 06 Nov 1994 08:49:37 GMT`. Times before the Unix epoch are clamped to it.
+pub fn format(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_secs();
+    let days = secs / 86400;
+    let secs_of_day = secs % 86400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        DAY_NAMES[(days % 7) as usize],
+        day,
+        MONTH_NAMES[(month - 1) as usize],
+        year,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+/// Parse an HTTP-date in any of the formats which recipients must accept:
+/// IMF-fixdate, obsolete RFC 850 format and ANSI C's asctime() format.
+/// Return `None` if the date is malformed or before the Unix epoch.
+pub fn parse(date: &str) -> Option<SystemTime> {
+    let tokens = date.split_whitespace().collect::<Vec<&str>>();
+    let (day_name, day, month, year, time) = match tokens.as_slice() {
+        // Sun, 06 Nov 1994 08:49:37 GMT
+        [day_name, day, month, year, time, "GMT"] if day.len() == 2 && year.len() == 4 => {
+            let day_name = day_name.strip_suffix(',')?;
+            (day_name, *day, *month, year.parse().ok()?, *time)
+        }
+        // Sunday, 06-Nov-94 08:49:37 GMT
+        [day_name, date, time, "GMT"] => {
+            let day_name = day_name.strip_suffix(',')?;
+            let day_name = DAY_NAMES[LONG_DAY_NAMES.iter().position(|name| *name == day_name)?];
+            let mut date = date.split('-');
+            let (day, month, year) = (date.next()?, date.next()?, date.next()?);
+            if day.len() != 2 || year.len() != 2 || date.next().is_some() {
+                return None;
+            }
+            // Two-digit years are interpreted as in the range from 1970 to 2069.
+            let year: u64 = year.parse().ok()?;
+            let year = if year < 70 { 2000 + year } else { 1900 + year };
+            (day_name, day, month, year, *time)
+        }
+        // Sun Nov  6 08:49:37 1994
+        [day_name, month, day, time, year] if year.len() == 4 => {
+            (*day_name, *day, *month, year.parse().ok()?, *time)
+        }
+        _ => return None,
+    };
+
+    if !day.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let day: u64 = day.parse().ok()?;
+    let month = MONTH_NAMES.iter().position(|name| *name == month)? as u64 + 1;
+    if year < 1970 || day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+    let mut time = time.split(':');
+    let mut next = |max: u64| -> Option<u64> {
+        let value = time.next()?;
+        if value.len() != 2 || !value.bytes().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        value.parse().ok().filter(|value| *value < max)
+    };
+    let (hour, minute, second) = (next(24)?, next(60)?, next(60)?);
+    if time.next().is_some() {
+        return None;
+    }
+
+    let days = days_from_civil(year, month, day);
+    if DAY_NAMES[(days % 7) as usize] != day_name {
+        return None;
+    }
+    let secs = days * 86400 + hour * 3600 + minute * 60 + second;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+fn is_leap_year(year: u64) -> bool {
+    year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400))
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Convert a date in the proleptic Gregorian calendar into days since the Unix epoch.
+/// The date must not be before the epoch.
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    // Count from 0000-03-01 as `civil_from_days` does.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let month_from_march = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month_from_march + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Convert days since the Unix epoch into a (year, month, day) date in the proleptic
+/// Gregorian calendar.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    // Count from 0000-03-01 so that a leap day is the last day of a year.
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = era * 400 + year_of_era + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::http_date::{format, parse};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format(UNIX_EPOCH), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(
+            format(UNIX_EPOCH + Duration::from_secs(784_111_777)),
+            "Sun, 06 Nov 1994 08:49:37 GMT"
+        );
+        // Leap day
+        assert_eq!(
+            format(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+            "Tue, 29 Feb 2000 00:00:00 GMT"
+        );
+        assert_eq!(
+            format(UNIX_EPOCH + Duration::from_secs(4_102_444_799)),
+            "Thu, 31 Dec 2099 23:59:59 GMT"
+        );
+    }
+
+    #[test]
+    fn test_format_before_epoch() {
+        assert_eq!(
+            format(UNIX_EPOCH - Duration::from_secs(1)),
+            "Thu, 01 Jan 1970 00:00:00 GMT"
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let expected = Some(UNIX_EPOCH + Duration::from_secs(784_111_777));
+        assert_eq!(parse("Sun, 06 Nov 1994 08:49:37 GMT"), expected);
+        assert_eq!(parse("Sunday, 06-Nov-94 08:49:37 GMT"), expected);
+        assert_eq!(parse("Sun Nov  6 08:49:37 1994"), expected);
+        assert_eq!(parse("Thu, 01 Jan 1970 00:00:00 GMT"), Some(UNIX_EPOCH));
+        assert_eq!(
+            parse("Tuesday, 29-Feb-00 00:00:00 GMT"),
+            Some(UNIX_EPOCH + Duration::from_secs(951_782_400))
+        );
+    }
+
+    #[test]
+    fn test_parse_round_trip() {
+        for secs in &[0, 59, 86_399, 951_782_400, 1_600_000_000, 4_102_444_799] {
+            let time = UNIX_EPOCH + Duration::from_secs(*secs);
+            assert_eq!(parse(&format(time)), Some(time));
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let dates = [
+            "",
+            "Sun, 06 Nov 1994 08:49:37",
+            "Sun, 06 Nov 1994 08:49:37 UTC",
+            "Mon, 06 Nov 1994 08:49:37 GMT",
+            "Sun 06 Nov 1994 08:49:37 GMT",
+            "Sun, 6 Nov 1994 08:49:37 GMT",
+            "Sun, 06 nov 1994 08:49:37 GMT",
+            "Sun, 06 Nov 1994 24:00:00 GMT",
+            "Sun, 06 Nov 1994 08:60:37 GMT",
+            "Sun, 06 Nov 1994 8:49:37 GMT",
+            "Sun, 06 Nov 1994 08:49:37:00 GMT",
+            "Sun, +6 Nov 1994 08:49:37 GMT",
+            "Thu, 29 Feb 1900 00:00:00 GMT",
+            "Wed, 31 Dec 1969 23:59:59 GMT",
+            "Sun, 06-Nov-94 08:49:37 GMT",
+            "Sunday, 06-Nov-1994 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 94",
+        ];
+        for date in &dates {
+            assert_eq!(parse(date), None, "{}", date);
+        }
+    }
+}
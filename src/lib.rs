@@ -1,7 +1,11 @@
 extern crate regex;
 
+pub mod connection;
 pub mod handler;
 pub mod headers;
+pub mod http_date;
+pub mod mime;
+pub mod range;
 pub mod request;
 pub mod responder;
 pub mod response;
@@ -9,3 +13,7 @@ pub mod router;
 pub mod server;
 pub mod static_files;
 pub mod status;
+pub mod test_client;
+pub mod thread_pool;
+pub mod typed_headers;
+pub mod uri;
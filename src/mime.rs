@@ -0,0 +1,81 @@
+use std::path::Path;
+
+/// MIME type of files whose type is unknown.
+pub const OCTET_STREAM: &str = "application/octet-stream";
+
+/// Return the MIME type of common web files from the extension, which is compared
+/// case-insensitively.
+pub fn from_extension(extension: &str) -> Option<&'static str> {
+    let mime = match extension.to_ascii_lowercase().as_str() {
+        // Text
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "txt" => "text/plain; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "xml" => "application/xml",
+        "json" | "map" => "application/json",
+        "webmanifest" => "application/manifest+json",
+        // Images
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "bmp" => "image/bmp",
+        // Fonts
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        // Audio and video
+        "mp3" => "audio/mpeg",
+        "ogg" => "audio/ogg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        // Others
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+/// Return the MIME type of the file from the extension of the path.
+/// If the type is unknown, return `application/octet-stream`.
+pub fn from_path<P: AsRef<Path>>(path: P) -> &'static str {
+    path.as_ref()
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .and_then(from_extension)
+        .unwrap_or(OCTET_STREAM)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mime::{from_extension, from_path, OCTET_STREAM};
+
+    #[test]
+    fn test_from_extension() {
+        assert_eq!(from_extension("png"), Some("image/png"));
+        assert_eq!(from_extension("PNG"), Some("image/png"));
+        assert_eq!(from_extension("woff2"), Some("font/woff2"));
+        assert_eq!(from_extension("unknown"), None);
+    }
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(from_path("static/index.html"), "text/html; charset=utf-8");
+        assert_eq!(from_path("app.wasm"), "application/wasm");
+        assert_eq!(from_path("archive.tar.gz"), "application/gzip");
+        assert_eq!(from_path("Makefile"), OCTET_STREAM);
+        assert_eq!(from_path(".png"), OCTET_STREAM);
+    }
+}
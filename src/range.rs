@@ -0,0 +1,397 @@
+use crate::response::Body;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+/// Requests with more ranges than this are served in full.
+const MAX_RANGES: usize = 64;
+
+/// Range of bytes in a representation. Both of `start` and `end` are inclusive.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    /// Return the number of bytes in the range, which is at least 1.
+    pub fn length(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// Format the range as the value of `Content-Range` header.
+    pub fn content_range(&self, complete_length: u64) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, complete_length)
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum RangeError {
+    /// The header is malformed and should be ignored.
+    Invalid,
+    /// None of the ranges overlaps with the representation.
+    Unsatisfiable,
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RangeError::Invalid => write!(f, "Range header is malformed"),
+            RangeError::Unsatisfiable => write!(f, "Range is not satisfiable"),
+        }
+    }
+}
+
+impl Error for RangeError {}
+
+/// Range specifier in `Range` header, which is not resolved against a representation yet.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RangeSpec {
+    /// `first-last`
+    FromTo(u64, u64),
+    /// `first-`
+    From(u64),
+    /// `-suffix_length`
+    Suffix(u64),
+}
+
+impl RangeSpec {
+    /// Resolve the specifier against the length of a representation, or return `None`
+    /// if it is unsatisfiable.
+    pub fn resolve(&self, complete_length: u64) -> Option<ByteRange> {
+        match *self {
+            RangeSpec::Suffix(suffix_length) => {
+                if suffix_length == 0 || complete_length == 0 {
+                    return None;
+                }
+                Some(ByteRange {
+                    start: complete_length.saturating_sub(suffix_length),
+                    end: complete_length - 1,
+                })
+            }
+            RangeSpec::FromTo(start, _) | RangeSpec::From(start) if start >= complete_length => {
+                None
+            }
+            RangeSpec::FromTo(start, end) => Some(ByteRange {
+                start,
+                end: end.min(complete_length - 1),
+            }),
+            RangeSpec::From(start) => Some(ByteRange {
+                start,
+                end: complete_length - 1,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for RangeSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RangeSpec::FromTo(first, last) => write!(f, "{}-{}", first, last),
+            RangeSpec::From(first) => write!(f, "{}-", first),
+            RangeSpec::Suffix(suffix_length) => write!(f, "-{}", suffix_length),
+        }
+    }
+}
+
+/// Parse the value of `Range` header such as `bytes=0-99, -100` into range specifiers.
+pub fn parse_specs(value: &str) -> Result<Vec<RangeSpec>, RangeError> {
+    let (unit, ranges) = value.split_once('=').ok_or(RangeError::Invalid)?;
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return Err(RangeError::Invalid);
+    }
+
+    let specs = ranges
+        .split(',')
+        .map(str::trim)
+        .filter(|spec| !spec.is_empty())
+        .collect::<Vec<&str>>();
+    if specs.is_empty() || specs.len() > MAX_RANGES {
+        return Err(RangeError::Invalid);
+    }
+    specs
+        .into_iter()
+        .map(|spec| {
+            let (first, last) = spec.split_once('-').ok_or(RangeError::Invalid)?;
+            let (first, last) = (first.trim(), last.trim());
+            if first.is_empty() {
+                return Ok(RangeSpec::Suffix(parse_position(last)?));
+            }
+            let first = parse_position(first)?;
+            if last.is_empty() {
+                return Ok(RangeSpec::From(first));
+            }
+            let last = parse_position(last)?;
+            if last < first {
+                return Err(RangeError::Invalid);
+            }
+            Ok(RangeSpec::FromTo(first, last))
+        })
+        .collect()
+}
+
+/// Parse the value of `Range` header and resolve it against the length of a
+/// representation. Unsatisfiable ranges are dropped unless all of them are.
+/// Overlapping and adjacent ranges are merged in ascending order, so that no byte is sent
+/// more than once as suggested in RFC 7233 section 6.1.
+pub fn parse(value: &str, complete_length: u64) -> Result<Vec<ByteRange>, RangeError> {
+    let mut satisfiable = parse_specs(value)?
+        .iter()
+        .filter_map(|spec| spec.resolve(complete_length))
+        .collect::<Vec<ByteRange>>();
+    if satisfiable.is_empty() {
+        return Err(RangeError::Unsatisfiable);
+    }
+    satisfiable.sort_by_key(|range| range.start);
+    let mut merged: Vec<ByteRange> = Vec::with_capacity(satisfiable.len());
+    for range in satisfiable {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end.saturating_add(1) => {
+                last.end = last.end.max(range.end);
+            }
+            _ => merged.push(range),
+        }
+    }
+    Ok(merged)
+}
+
+fn parse_position(s: &str) -> Result<u64, RangeError> {
+    if s.is_empty() || !s.bytes().all(|c| c.is_ascii_digit()) {
+        return Err(RangeError::Invalid);
+    }
+    // Positions too large to represent are beyond the end of any representation.
+    Ok(s.parse().unwrap_or(u64::MAX))
+}
+
+enum Part {
+    Bytes(Cursor<Vec<u8>>),
+    Range { start: u64, remaining: u64 },
+}
+
+/// Reader of a `multipart/byteranges` body which reads each range from the source on demand.
+struct MultipartReader<R> {
+    source: R,
+    parts: VecDeque<Part>,
+    /// Whether the source is at the start of the current range.
+    positioned: bool,
+}
+
+impl<R: Read + Seek> Read for MultipartReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = match self.parts.front_mut() {
+                None => return Ok(0),
+                Some(Part::Bytes(bytes)) => bytes.read(buf)?,
+                Some(Part::Range { start, remaining }) => {
+                    if *remaining == 0 {
+                        0
+                    } else {
+                        if !self.positioned {
+                            self.source.seek(SeekFrom::Start(*start))?;
+                            self.positioned = true;
+                        }
+                        let n = (&mut self.source).take(*remaining).read(buf)?;
+                        if n == 0 {
+                            return Err(io::Error::new(
+                                io::ErrorKind::UnexpectedEof,
+                                "Source is shorter than the range",
+                            ));
+                        }
+                        *start += n as u64;
+                        *remaining -= n as u64;
+                        n
+                    }
+                }
+            };
+            if n > 0 {
+                return Ok(n);
+            }
+            self.parts.pop_front();
+            self.positioned = false;
+        }
+    }
+}
+
+/// Create a `multipart/byteranges` body of the ranges in the source, which is a
+/// representation of `content_type` with length `complete_length`.
+/// The content type of the body is `multipart/byteranges; boundary={boundary}`.
+pub fn multipart_body<R>(
+    source: R,
+    ranges: &[ByteRange],
+    content_type: &str,
+    complete_length: u64,
+    boundary: &str,
+) -> Body
+where
+    R: Read + Seek + Send + 'static,
+{
+    let mut parts = VecDeque::new();
+    let mut length = 0;
+    for (i, range) in ranges.iter().enumerate() {
+        let delimiter = if i == 0 { "" } else { "\r\n" };
+        let head = format!(
+            "{}--{}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+            delimiter,
+            boundary,
+            content_type,
+            range.content_range(complete_length)
+        );
+        length += head.len() as u64 + range.length();
+        parts.push_back(Part::Bytes(Cursor::new(head.into_bytes())));
+        parts.push_back(Part::Range {
+            start: range.start,
+            remaining: range.length(),
+        });
+    }
+    let close_delimiter = format!("\r\n--{}--\r\n", boundary);
+    length += close_delimiter.len() as u64;
+    parts.push_back(Part::Bytes(Cursor::new(close_delimiter.into_bytes())));
+
+    let reader = MultipartReader {
+        source,
+        parts,
+        positioned: false,
+    };
+    Body::from_reader(reader, length)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::range::{multipart_body, parse, parse_specs, ByteRange, RangeError, RangeSpec};
+    use std::io::Cursor;
+
+    fn range(start: u64, end: u64) -> ByteRange {
+        ByteRange { start, end }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("bytes=0-499", 10000), Ok(vec![range(0, 499)]));
+        assert_eq!(parse("bytes=500-999", 10000), Ok(vec![range(500, 999)]));
+        assert_eq!(parse("bytes=-500", 10000), Ok(vec![range(9500, 9999)]));
+        assert_eq!(parse("bytes=9500-", 10000), Ok(vec![range(9500, 9999)]));
+        assert_eq!(
+            parse("bytes=0-0, -1", 10000),
+            Ok(vec![range(0, 0), range(9999, 9999)])
+        );
+        assert_eq!(
+            parse("Bytes = 1-2 ,, 4-5", 10),
+            Ok(vec![range(1, 2), range(4, 5)])
+        );
+    }
+
+    #[test]
+    fn test_parse_specs() {
+        assert_eq!(
+            parse_specs("bytes=0-1, 5-, -3"),
+            Ok(vec![
+                RangeSpec::FromTo(0, 1),
+                RangeSpec::From(5),
+                RangeSpec::Suffix(3)
+            ])
+        );
+        let specs = parse_specs("bytes=0-1,5-,-3").unwrap();
+        let specs = specs
+            .iter()
+            .map(|spec| spec.to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(specs, vec!["0-1", "5-", "-3"]);
+    }
+
+    #[test]
+    fn test_parse_clamps_to_length() {
+        assert_eq!(parse("bytes=5-100", 10), Ok(vec![range(5, 9)]));
+        assert_eq!(parse("bytes=-100", 10), Ok(vec![range(0, 9)]));
+        assert_eq!(
+            parse("bytes=0-99999999999999999999999", 10),
+            Ok(vec![range(0, 9)])
+        );
+        // Unsatisfiable ranges are dropped if others are satisfiable.
+        assert_eq!(parse("bytes=20-30, 0-1", 10), Ok(vec![range(0, 1)]));
+    }
+
+    #[test]
+    fn test_parse_merges_ranges() {
+        let same = format!("bytes={}", vec!["0-"; 64].join(","));
+        assert_eq!(parse(&same, 10), Ok(vec![range(0, 9)]));
+        assert_eq!(parse("bytes=0-4, 2-6", 10), Ok(vec![range(0, 6)]));
+        assert_eq!(parse("bytes=0-1, 2-3", 10), Ok(vec![range(0, 3)]));
+        assert_eq!(
+            parse("bytes=-3, 0-1, 1-2", 10),
+            Ok(vec![range(0, 2), range(7, 9)])
+        );
+        assert_eq!(
+            parse("bytes=5-6, 0-1", 10),
+            Ok(vec![range(0, 1), range(5, 6)])
+        );
+        assert_eq!(parse("bytes=1-8, 2-3, -5", 10), Ok(vec![range(1, 9)]));
+    }
+
+    #[test]
+    fn test_parse_unsatisfiable() {
+        assert_eq!(parse("bytes=10-20", 10), Err(RangeError::Unsatisfiable));
+        assert_eq!(parse("bytes=-0", 10), Err(RangeError::Unsatisfiable));
+        assert_eq!(parse("bytes=0-", 0), Err(RangeError::Unsatisfiable));
+        assert_eq!(parse("bytes=-1", 0), Err(RangeError::Unsatisfiable));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let values = [
+            "",
+            "bytes",
+            "bytes=",
+            "items=0-1",
+            "bytes=1",
+            "bytes=2-1",
+            "bytes=-",
+            "bytes=a-b",
+            "bytes=+1-2",
+            "bytes=0-1-2",
+        ];
+        for value in &values {
+            assert_eq!(parse(value, 10), Err(RangeError::Invalid), "{}", value);
+        }
+        let too_many = format!("bytes={}", vec!["0-0"; 65].join(","));
+        assert_eq!(parse(&too_many, 10), Err(RangeError::Invalid));
+    }
+
+    #[test]
+    fn test_multipart_body() {
+        let source = Cursor::new(b"0123456789".to_vec());
+        let body = multipart_body(
+            source,
+            &[range(0, 1), range(7, 9)],
+            "text/plain",
+            10,
+            "BOUNDARY",
+        );
+        let length = body.len();
+        let body = String::from_utf8(body.into_bytes().unwrap()).unwrap();
+        assert_eq!(
+            body,
+            concat!(
+                "--BOUNDARY\r\n",
+                "Content-Type: text/plain\r\n",
+                "Content-Range: bytes 0-1/10\r\n",
+                "\r\n",
+                "01\r\n",
+                "--BOUNDARY\r\n",
+                "Content-Type: text/plain\r\n",
+                "Content-Range: bytes 7-9/10\r\n",
+                "\r\n",
+                "789\r\n",
+                "--BOUNDARY--\r\n"
+            )
+        );
+        assert_eq!(length, body.len() as u64);
+    }
+
+    #[test]
+    fn test_multipart_body_with_short_source() {
+        let source = Cursor::new(b"01234".to_vec());
+        let body = multipart_body(source, &[range(3, 9)], "text/plain", 10, "B");
+        assert!(body.into_bytes().is_err());
+    }
+}
@@ -1,12 +1,43 @@
-use crate::headers::{HeaderField, HeaderMap};
+use crate::headers::{HeaderMap, HeaderName};
+use crate::typed_headers::{ContentLength, TypedHeader, TypedHeaderError};
+use crate::uri::{parse_query, percent_decode};
 use regex::Regex;
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::error::Error;
 use std::str::FromStr;
+use std::sync::OnceLock;
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum Method {
     Get,
+    Post,
+    Put,
+    Delete,
+    Patch,
+    Head,
+    Options,
+    Trace,
+    Connect,
+    /// Method not defined in RFC 7231 and RFC 5789, such as WebDAV's `PROPFIND`.
+    Extension(String),
+}
+
+impl Method {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Method::Get => "GET",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Patch => "PATCH",
+            Method::Head => "HEAD",
+            Method::Options => "OPTIONS",
+            Method::Trace => "TRACE",
+            Method::Connect => "CONNECT",
+            Method::Extension(method) => method,
+        }
+    }
 }
 
 impl FromStr for Method {
@@ -14,103 +45,369 @@ impl FromStr for Method {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let method = match s {
             "GET" => Method::Get,
+            "POST" => Method::Post,
+            "PUT" => Method::Put,
+            "DELETE" => Method::Delete,
+            "PATCH" => Method::Patch,
+            "HEAD" => Method::Head,
+            "OPTIONS" => Method::Options,
+            "TRACE" => Method::Trace,
+            "CONNECT" => Method::Connect,
+            _ if is_token(s) => Method::Extension(s.to_string()),
             _ => return Err(RequestParseError::InvalidMethod),
         };
         Ok(method)
     }
 }
 
+impl std::fmt::Display for Method {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Check if the string is a token defined in RFC 7230, which is used for method names and
+/// header field names.
+pub(crate) fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes().all(|c| {
+            c.is_ascii_alphanumeric()
+                || matches!(
+                    c,
+                    b'!' | b'#'
+                        | b'$'
+                        | b'%'
+                        | b'&'
+                        | b'\''
+                        | b'*'
+                        | b'+'
+                        | b'-'
+                        | b'.'
+                        | b'^'
+                        | b'_'
+                        | b'`'
+                        | b'|'
+                        | b'~'
+                )
+        })
+}
+
+/// Check if the request target is authority-form, which is `host:port` without scheme,
+/// user information and path.
+fn is_authority_form(target: &str) -> bool {
+    match target.rsplit_once(':') {
+        Some((host, port)) => {
+            !host.is_empty()
+                && !host.contains(['/', '?', '#', '@'])
+                && !port.is_empty()
+                && port.bytes().all(|c| c.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Version {
+    Http10,
+    Http11,
+}
+
+impl FromStr for Version {
+    type Err = RequestParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "HTTP/1.0" => Ok(Version::Http10),
+            "HTTP/1.1" => Ok(Version::Http11),
+            _ => Err(RequestParseError::InvalidVersion),
+        }
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Version::Http10 => write!(f, "HTTP/1.0"),
+            Version::Http11 => write!(f, "HTTP/1.1"),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Request {
     pub method: Method,
+    /// Request target as received, such as `/search?q=a%20b`.
     pub uri: String,
+    /// Percent-decoded path of the request target, such as `/search`.
+    pub path: String,
+    /// Pairs of names and values in the query string in order.
+    pub query: Vec<(String, String)>,
+    pub version: Version,
     pub headers: HeaderMap,
+    pub body: Vec<u8>,
+    /// Values of path parameters such as `:id`, which are set by the router.
+    /// The path matched by a wildcard is stored as `*`.
+    pub params: HashMap<String, String>,
 }
 
 impl Request {
-    /// Parse a request string and create new `Request` instance.
-    // Todo: parse request body.
-    pub fn new(request_str: &str) -> Result<Request, Box<dyn Error>> {
-        let request_lines = request_str.split("\r\n").collect::<Vec<&str>>();
+    /// Parse a request and create new `Request` instance.
+    /// The request body is read according to `Content-Length` header.
+    pub fn new(request: &[u8]) -> Result<Request, Box<dyn Error>> {
+        let head_length = Self::head_length(request).ok_or(RequestParseError::IncompleteHead)?;
+        let head = std::str::from_utf8(&request[..head_length])?;
+        let mut parsed = Self::parse_head(head)?;
+        let body = &request[head_length..];
+        let content_length = parsed.content_length()?;
+        if body.len() < content_length {
+            return Err(Box::new(RequestParseError::IncompleteBody));
+        }
+        parsed.body = body[..content_length].to_vec();
+        Ok(parsed)
+    }
+
+    /// Return length of the request line and headers including the empty line which
+    /// terminates them, or `None` if the empty line has not been received yet.
+    pub fn head_length(request: &[u8]) -> Option<usize> {
+        request
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .map(|position| position + 4)
+    }
+
+    /// Parse a request line and headers. Body of returned `Request` is empty.
+    pub fn parse_head(head: &str) -> Result<Request, Box<dyn Error>> {
+        let request_lines = head.split("\r\n").collect::<Vec<&str>>();
         let (request_line, header_lines) = request_lines
             .split_first()
-            .ok_or_else(|| RequestParseError::Empty)?;
-        let (method, uri) = Self::parse_request_line(*request_line)?;
+            .ok_or(RequestParseError::Empty)?;
+        let (method, uri, version) = Self::parse_request_line(request_line)?;
+        let (path, query) = Self::parse_target(&uri)?;
         let headers = Self::parse_headers(header_lines)?;
 
         Ok(Request {
             method,
             uri,
+            path,
+            query,
+            version,
             headers,
+            body: Vec::new(),
+            params: HashMap::new(),
         })
     }
 
-    /// Parse first line of request. Return method type and uri of the request.
-    fn parse_request_line(request_line_str: &str) -> Result<(Method, String), Box<dyn Error>> {
-        let request_line_regex = Regex::new(r"([A-Z]+) ((/.*)*) HTTP/[1,2].\d{1}")?;
+    /// Return length of the body declared by `Content-Length` header.
+    /// If the header is absent, the request has no body. Values other than digits and
+    /// differing repeated values are rejected as described in RFC 7230 section 3.3.3.
+    /// Requests with `Transfer-Encoding` are rejected since their bodies cannot be framed.
+    pub fn content_length(&self) -> Result<usize, RequestParseError> {
+        if self.headers.contains_key(&HeaderName::TRANSFER_ENCODING) {
+            return Err(if self.headers.contains_key(&HeaderName::CONTENT_LENGTH) {
+                RequestParseError::InvalidContentLength
+            } else {
+                RequestParseError::UnsupportedTransferEncoding
+            });
+        }
+        match self.typed_header::<ContentLength>() {
+            Ok(ContentLength(length)) => {
+                usize::try_from(length).map_err(|_| RequestParseError::InvalidContentLength)
+            }
+            Err(TypedHeaderError::Missing(_)) => Ok(0),
+            Err(TypedHeaderError::Invalid(_)) => Err(RequestParseError::InvalidContentLength),
+        }
+    }
+
+    /// Check if the connection should be kept open after responding to this request.
+    /// HTTP/1.1 connections are persistent unless `Connection: close` is sent, and HTTP/1.0
+    /// connections are closed unless `Connection: keep-alive` is sent.
+    pub fn keep_alive(&self) -> bool {
+        let has_option = |option: &str| {
+            self.headers
+                .get_list(&HeaderName::CONNECTION)
+                .any(|token| token.eq_ignore_ascii_case(option))
+        };
+        match self.version {
+            Version::Http10 => has_option("keep-alive"),
+            Version::Http11 => !has_option("close"),
+        }
+    }
+
+    /// Decode the header such as `ContentType` from the request headers.
+    pub fn typed_header<H: TypedHeader>(&self) -> Result<H, TypedHeaderError> {
+        self.headers.typed_get()
+    }
+
+    /// Return the first value of the query parameter such as `page` in `/?page=2`.
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Return all the values of the query parameter such as `tag` in `/?tag=a&tag=b`.
+    pub fn query_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.query
+            .iter()
+            .filter(move |(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Return the value of the path parameter such as `id` for the route `/users/:id`.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(|value| value.as_str())
+    }
+
+    /// Return the rest of the path matched by a wildcard such as `/static/*`.
+    pub fn wildcard(&self) -> Option<&str> {
+        self.param("*")
+    }
+
+    /// Parse first line of request. Return method type, uri and HTTP version of the request.
+    fn parse_request_line(
+        request_line_str: &str,
+    ) -> Result<(Method, String, Version), Box<dyn Error>> {
+        static REQUEST_LINE_REGEX: OnceLock<Regex> = OnceLock::new();
+        let request_line_regex = REQUEST_LINE_REGEX.get_or_init(|| {
+            Regex::new(r"^([!#$%&'*+\-.^_`|~0-9A-Za-z]+) (\S+) (HTTP/\d\.\d)$").unwrap()
+        });
         let caps = request_line_regex
             .captures(request_line_str)
-            .ok_or_else(|| RequestParseError::Empty)?;
+            .ok_or(RequestParseError::Empty)?;
         let method = caps
             .get(1)
-            .ok_or_else(|| RequestParseError::InvalidMethod)?
+            .ok_or(RequestParseError::InvalidMethod)?
             .as_str();
         let method = Method::from_str(method)?;
         let uri = caps
             .get(2)
-            .ok_or_else(|| RequestParseError::LackingPath)?
+            .ok_or(RequestParseError::InvalidTarget)?
             .as_str()
             .to_string();
-        // let uri = uri.trim_start_matches('/').to_string();
-        Ok((method, uri))
+        // CONNECT takes authority-form such as `example.com:443`, and OPTIONS takes `*` to
+        // ask about the whole server. Other targets are origin-form.
+        let is_valid_target = uri.starts_with('/')
+            || (method == Method::Connect && is_authority_form(&uri))
+            || (method == Method::Options && uri == "*");
+        if !is_valid_target {
+            return Err(Box::new(RequestParseError::InvalidTarget));
+        }
+        let version = caps
+            .get(3)
+            .ok_or(RequestParseError::InvalidVersion)?
+            .as_str();
+        let version = Version::from_str(version)?;
+        Ok((method, uri, version))
+    }
+
+    /// Split the request target into the percent-decoded path and the query.
+    fn parse_target(uri: &str) -> Result<(String, Vec<(String, String)>), RequestParseError> {
+        // Fragments are not sent by clients, but ignored if they are.
+        let uri = uri.split('#').next().unwrap_or(uri);
+        let (path, query) = uri.split_once('?').unwrap_or((uri, ""));
+        let path = percent_decode(path).ok_or(RequestParseError::InvalidPercentEncoding)?;
+        let query = parse_query(query).ok_or(RequestParseError::InvalidPercentEncoding)?;
+        Ok((path, query))
     }
 
     /// Parse request lines except for the first line of it and return a map of
-    /// header field and its value.
-    // Todo: return remaining request lines.
+    /// header field and its value, as defined in RFC 7230 section 3.2.
+    /// Line numbers in errors count the request line as line 1.
     pub(crate) fn parse_headers(header_lines: &[&str]) -> Result<HeaderMap, RequestParseError> {
-        let mut headers = HashMap::new();
-        let header_lines = header_lines.to_vec();
-        let mut header_lines = header_lines.iter();
-        while let Some(header_line) = header_lines.next() {
-            if *header_line == "" {
+        let mut headers = HeaderMap::new();
+        for (i, header_line) in header_lines.iter().enumerate() {
+            let line_number = i + 2;
+            if header_line.is_empty() {
                 break;
             }
-            let header_line = header_line.split(": ").collect::<Vec<&str>>();
-            let header_field = header_line
-                .first()
-                .ok_or_else(|| RequestParseError::InvalidHeaderFormat)?;
-            let header_field = HeaderField::from_str(header_field).unwrap();
-            let header_value = header_line
-                .iter()
-                .nth(1)
-                .ok_or_else(|| RequestParseError::InvalidHeaderFormat)?
-                .clone();
-            // Unrecognized header fields should be ignored.
-            match header_field {
-                HeaderField::Undefined => continue,
-                _ => headers.insert(header_field, header_value.to_string()),
-            };
+            // A line starting with whitespace continues the previous one, which is
+            // obsolete line folding.
+            if header_line.starts_with([' ', '\t']) {
+                return Err(RequestParseError::ObsoleteLineFolding(line_number));
+            }
+            let (header_name, header_value) = header_line
+                .split_once(':')
+                .ok_or(RequestParseError::MissingColon(line_number))?;
+            if header_name.ends_with([' ', '\t']) {
+                return Err(RequestParseError::WhitespaceBeforeColon(line_number));
+            }
+            let header_name = HeaderName::from_str(header_name)
+                .map_err(|_| RequestParseError::InvalidHeaderName(line_number))?;
+            let header_value = header_value.trim_matches([' ', '\t']);
+            if header_value
+                .chars()
+                .any(|c| c != '\t' && c.is_ascii_control())
+            {
+                return Err(RequestParseError::InvalidHeaderValue(line_number));
+            }
+            headers.append(header_name, header_value);
         }
         Ok(headers)
     }
 }
 
-#[derive(Clone, Debug)]
+/// Errors of parsing a request. Errors of a header line have the line number of it.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum RequestParseError {
     Empty,
+    IncompleteHead,
+    HeadTooLarge,
     InvalidMethod,
-    LackingPath,
+    /// The request target is not in the form allowed for the method.
+    InvalidTarget,
+    InvalidVersion,
+    /// The path or the query has a malformed percent-encoding or is not valid UTF-8.
+    InvalidPercentEncoding,
     InvalidHeaderFormat,
+    MissingColon(usize),
+    WhitespaceBeforeColon(usize),
+    ObsoleteLineFolding(usize),
+    InvalidHeaderName(usize),
+    InvalidHeaderValue(usize),
+    /// `Content-Length` is malformed, or sent with `Transfer-Encoding`.
+    InvalidContentLength,
+    /// `Transfer-Encoding` such as chunked is not supported.
+    UnsupportedTransferEncoding,
+    IncompleteBody,
+    BodyTooLarge,
 }
 
 impl std::fmt::Display for RequestParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            &RequestParseError::Empty => write!(f, "Empty request"),
-            &RequestParseError::InvalidMethod => write!(f, "Invalid type of method"),
-            &RequestParseError::LackingPath => write!(f, "Lacking path"),
-            &RequestParseError::InvalidHeaderFormat => write!(f, "Invalid form of header"),
+            RequestParseError::Empty => write!(f, "Empty request"),
+            RequestParseError::IncompleteHead => write!(f, "Request head is not terminated"),
+            RequestParseError::HeadTooLarge => write!(f, "Request head is too large"),
+            RequestParseError::InvalidMethod => write!(f, "Invalid type of method"),
+            RequestParseError::InvalidTarget => write!(f, "Invalid request target"),
+            RequestParseError::InvalidVersion => write!(f, "Unsupported HTTP version"),
+            RequestParseError::InvalidPercentEncoding => {
+                write!(f, "Invalid percent-encoding in request target")
+            }
+            RequestParseError::InvalidHeaderFormat => write!(f, "Invalid form of header"),
+            RequestParseError::MissingColon(line) => {
+                write!(f, "Header at line {} has no colon", line)
+            }
+            RequestParseError::WhitespaceBeforeColon(line) => {
+                write!(f, "Header at line {} has whitespace before colon", line)
+            }
+            RequestParseError::ObsoleteLineFolding(line) => {
+                write!(f, "Header at line {} is folded", line)
+            }
+            RequestParseError::InvalidHeaderName(line) => {
+                write!(f, "Header at line {} has invalid name", line)
+            }
+            RequestParseError::InvalidHeaderValue(line) => {
+                write!(f, "Header at line {} has invalid value", line)
+            }
+            RequestParseError::InvalidContentLength => write!(f, "Invalid Content-Length"),
+            RequestParseError::UnsupportedTransferEncoding => {
+                write!(f, "Unsupported Transfer-Encoding")
+            }
+            RequestParseError::IncompleteBody => {
+                write!(f, "Request body is shorter than Content-Length")
+            }
+            RequestParseError::BodyTooLarge => write!(f, "Request body is too large"),
         }
     }
 }
@@ -119,23 +416,111 @@ impl Error for RequestParseError {}
 
 #[cfg(test)]
 mod tests {
-    use crate::request::{HeaderField, Method, Request};
-    use std::collections::HashMap;
+    use crate::headers::HeaderMap;
+    use crate::request::{HeaderName, Method, Request, RequestParseError, Version};
 
     #[test]
     fn test_parse_request_line_for_root() {
         let request_line = "GET / HTTP/1.1";
-        let (method, path) = Request::parse_request_line(request_line).unwrap();
+        let (method, path, version) = Request::parse_request_line(request_line).unwrap();
         assert_eq!(method, Method::Get);
-        assert_eq!(path, "/".to_string())
+        assert_eq!(path, "/".to_string());
+        assert_eq!(version, Version::Http11);
     }
 
     #[test]
     fn test_parse_request_line_for_index() {
-        let request_line = "GET /www/index.html HTTP/1.1";
-        let (method, path) = Request::parse_request_line(request_line).unwrap();
+        let request_line = "GET /www/index.html HTTP/1.0";
+        let (method, path, version) = Request::parse_request_line(request_line).unwrap();
         assert_eq!(method, Method::Get);
-        assert_eq!(path, "/www/index.html".to_string())
+        assert_eq!(path, "/www/index.html".to_string());
+        assert_eq!(version, Version::Http10);
+    }
+
+    #[test]
+    fn test_parse_request_line_for_methods() {
+        let methods = [
+            ("GET", Method::Get),
+            ("POST", Method::Post),
+            ("PUT", Method::Put),
+            ("DELETE", Method::Delete),
+            ("PATCH", Method::Patch),
+            ("HEAD", Method::Head),
+            ("OPTIONS", Method::Options),
+            ("TRACE", Method::Trace),
+            ("CONNECT", Method::Connect),
+            ("PROPFIND", Method::Extension("PROPFIND".to_string())),
+            ("M-SEARCH", Method::Extension("M-SEARCH".to_string())),
+        ];
+        for (method_str, expected) in &methods {
+            let request_line = format!("{} /users HTTP/1.1", method_str);
+            let (method, path, _) = Request::parse_request_line(&request_line).unwrap();
+            assert_eq!(&method, expected);
+            assert_eq!(method.to_string(), *method_str);
+            assert_eq!(path, "/users".to_string());
+        }
+    }
+
+    #[test]
+    fn test_parse_request_line_for_connect_and_options() {
+        let (method, target, _) =
+            Request::parse_request_line("CONNECT example.com:443 HTTP/1.1").unwrap();
+        assert_eq!(method, Method::Connect);
+        assert_eq!(target, "example.com:443");
+        let (method, target, _) =
+            Request::parse_request_line("CONNECT [::1]:8080 HTTP/1.1").unwrap();
+        assert_eq!(method, Method::Connect);
+        assert_eq!(target, "[::1]:8080");
+        let (method, target, _) = Request::parse_request_line("OPTIONS * HTTP/1.1").unwrap();
+        assert_eq!(method, Method::Options);
+        assert_eq!(target, "*");
+
+        let invalid_lines = [
+            "GET example.com:443 HTTP/1.1",
+            "GET * HTTP/1.1",
+            "CONNECT example.com HTTP/1.1",
+            "CONNECT example.com: HTTP/1.1",
+            "CONNECT user@example.com:443 HTTP/1.1",
+            "CONNECT * HTTP/1.1",
+            "OPTIONS example.com:443 HTTP/1.1",
+        ];
+        for request_line in &invalid_lines {
+            let err = Request::parse_request_line(request_line).unwrap_err();
+            assert_eq!(
+                err.downcast_ref::<RequestParseError>(),
+                Some(&RequestParseError::InvalidTarget),
+                "{}",
+                request_line
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_request_line_for_invalid_method() {
+        assert!(Request::parse_request_line("GE(T / HTTP/1.1").is_err());
+        assert!(Request::parse_request_line(" / HTTP/1.1").is_err());
+        assert!("GE T".parse::<Method>().is_err());
+    }
+
+    #[test]
+    fn test_parse_request_line_for_invalid_version() {
+        assert!(Request::parse_request_line("GET / HTTP/2.0").is_err());
+        assert!(Request::parse_request_line("GET / HTTP/1.1 ").is_err());
+        assert!(Request::parse_request_line("GET / FTP/1.1").is_err());
+    }
+
+    #[test]
+    fn test_keep_alive() {
+        let keep_alive = |request: &[u8]| Request::new(request).unwrap().keep_alive();
+        assert!(keep_alive(b"GET / HTTP/1.1\r\n\r\n"));
+        assert!(!keep_alive(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n"));
+        assert!(!keep_alive(
+            b"GET / HTTP/1.1\r\nConnection: Upgrade, Close\r\n\r\n"
+        ));
+        assert!(!keep_alive(b"GET / HTTP/1.0\r\n\r\n"));
+        assert!(keep_alive(
+            b"GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n"
+        ));
     }
 
     #[test]
@@ -151,18 +536,219 @@ mod tests {
         assert_eq!(
             headers,
             [
-                (HeaderField::ContentLength, "3".to_string()),
-                (HeaderField::UserAgent, "curl/7.58.0".to_string()),
-                (HeaderField::Host, "localhost:8000".to_string()),
-                (HeaderField::Accept, "*/*".to_string()),
+                (HeaderName::HOST, "localhost:8000".to_string()),
+                (HeaderName::USER_AGENT, "curl/7.58.0".to_string()),
+                (HeaderName::ACCEPT, "*/*".to_string()),
+                (HeaderName::CONTENT_LENGTH, "3".to_string()),
                 (
-                    HeaderField::ContentType,
+                    HeaderName::CONTENT_TYPE,
                     "application/x-www-form-urlencoded".to_string()
                 ),
             ]
             .iter()
             .cloned()
-            .collect::<HashMap<HeaderField, String>>()
+            .collect::<HeaderMap>()
+        );
+    }
+
+    #[test]
+    fn test_parse_headers_keeps_all_headers() {
+        let header_lines = [
+            "authorization: Bearer token",
+            "Cookie: a=b",
+            "X-Request-Id: 42",
+        ];
+        let headers = Request::parse_headers(&header_lines).unwrap();
+        assert_eq!(headers.len(), 3);
+        assert_eq!(&headers[&HeaderName::AUTHORIZATION], "Bearer token");
+        assert_eq!(&headers[&HeaderName::COOKIE], "a=b");
+        assert_eq!(&headers[&HeaderName::from_static("x-request-id")], "42");
+        let names = headers
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(names, vec!["Authorization", "Cookie", "X-Request-Id"]);
+    }
+
+    #[test]
+    fn test_parse_headers_whitespace() {
+        let header_lines = [
+            "Host:localhost",
+            "Referer: http://example.com:8000/a: b",
+            "Accept: \t text/html, */* \t",
+            "X-Empty:",
+            "X-Tab: a\tb",
+        ];
+        let headers = Request::parse_headers(&header_lines).unwrap();
+        assert_eq!(headers.get(&HeaderName::HOST), Some("localhost"));
+        assert_eq!(
+            headers.get(&HeaderName::REFERER),
+            Some("http://example.com:8000/a: b")
+        );
+        assert_eq!(headers.get(&HeaderName::ACCEPT), Some("text/html, */*"));
+        assert_eq!(headers.get(&HeaderName::from_static("X-Empty")), Some(""));
+        assert_eq!(headers.get(&HeaderName::from_static("X-Tab")), Some("a\tb"));
+    }
+
+    #[test]
+    fn test_parse_headers_invalid() {
+        let cases = [
+            ("Host localhost", RequestParseError::MissingColon(3)),
+            (
+                "Host : localhost",
+                RequestParseError::WhitespaceBeforeColon(3),
+            ),
+            (
+                "Host\t: localhost",
+                RequestParseError::WhitespaceBeforeColon(3),
+            ),
+            (" folded", RequestParseError::ObsoleteLineFolding(3)),
+            ("\tfolded", RequestParseError::ObsoleteLineFolding(3)),
+            ("X Id: 1", RequestParseError::InvalidHeaderName(3)),
+            (": empty name", RequestParseError::InvalidHeaderName(3)),
+            ("X-(Id): 1", RequestParseError::InvalidHeaderName(3)),
+            ("X-Id: a\u{0}b", RequestParseError::InvalidHeaderValue(3)),
+            ("X-Id: a\u{7f}b", RequestParseError::InvalidHeaderValue(3)),
+            ("X-Id: a\rb", RequestParseError::InvalidHeaderValue(3)),
+        ];
+        for (line, expected) in &cases {
+            let result = Request::parse_headers(&["Accept: */*", line]);
+            assert_eq!(result.err().as_ref(), Some(expected), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn test_new_with_query() {
+        let request =
+            Request::new(b"GET /search%20results?q=a+b&tag=x&tag=%E3%81%82&empty HTTP/1.1\r\n\r\n")
+                .unwrap();
+        assert_eq!(
+            request.uri,
+            "/search%20results?q=a+b&tag=x&tag=%E3%81%82&empty"
+        );
+        assert_eq!(request.path, "/search results");
+        assert_eq!(request.query("q"), Some("a b"));
+        assert_eq!(request.query("tag"), Some("x"));
+        assert_eq!(
+            request.query_all("tag").collect::<Vec<&str>>(),
+            vec!["x", "あ"]
         );
+        assert_eq!(request.query("empty"), Some(""));
+        assert_eq!(request.query("missing"), None);
+        assert_eq!(request.query_all("missing").count(), 0);
+
+        let request = Request::new(b"GET /a?#fragment HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(request.path, "/a");
+        assert!(request.query.is_empty());
+    }
+
+    #[test]
+    fn test_new_with_invalid_percent_encoding() {
+        let targets = ["/a%2", "/a%zz", "/%c0%ae", "/?q=%", "/?%G0=1"];
+        for target in &targets {
+            let request = format!("GET {} HTTP/1.1\r\n\r\n", target);
+            let err = Request::new(request.as_bytes()).unwrap_err();
+            assert_eq!(
+                err.downcast_ref::<RequestParseError>(),
+                Some(&RequestParseError::InvalidPercentEncoding),
+                "{}",
+                target
+            );
+        }
+    }
+
+    #[test]
+    fn test_new_with_folded_header() {
+        let request = b"GET / HTTP/1.1\r\nX-Id: a\r\n b\r\n\r\n";
+        let err = Request::new(request).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RequestParseError>(),
+            Some(RequestParseError::ObsoleteLineFolding(3))
+        ));
+    }
+
+    #[test]
+    fn test_new_with_lowercase_content_length() {
+        let request = b"POST /form HTTP/1.1\r\ncontent-length: 7\r\n\r\nname=ab";
+        let request = Request::new(request).unwrap();
+        assert_eq!(request.body, b"name=ab".to_vec());
+    }
+
+    #[test]
+    fn test_new_with_body() {
+        let request = b"POST /form HTTP/1.1\r\nContent-Length: 7\r\n\r\nname=ab";
+        let request = Request::new(request).unwrap();
+        assert_eq!(request.method, Method::Post);
+        assert_eq!(request.body, b"name=ab".to_vec());
+    }
+
+    #[test]
+    fn test_new_without_content_length() {
+        let request = Request::new(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        assert!(request.body.is_empty());
+    }
+
+    #[test]
+    fn test_new_ignores_bytes_after_body() {
+        let request = b"GET / HTTP/1.1\r\nContent-Length: 2\r\n\r\nabGET";
+        let request = Request::new(request).unwrap();
+        assert_eq!(request.body, b"ab".to_vec());
+    }
+
+    #[test]
+    fn test_new_with_repeated_content_length() {
+        let request = b"POST / HTTP/1.1\r\nContent-Length: 2\r\nContent-Length: 2\r\n\r\nab";
+        assert_eq!(Request::new(request).unwrap().body, b"ab".to_vec());
+
+        let requests: [&[u8]; 3] = [
+            b"POST / HTTP/1.1\r\nContent-Length: 0\r\nContent-Length: 5\r\n\r\nhello",
+            b"POST / HTTP/1.1\r\nContent-Length: 0, 5\r\n\r\nhello",
+            b"POST / HTTP/1.1\r\nContent-Length: +3\r\n\r\nabc",
+        ];
+        for request in &requests {
+            let err = Request::new(request).unwrap_err();
+            assert_eq!(
+                err.downcast_ref::<RequestParseError>(),
+                Some(&RequestParseError::InvalidContentLength)
+            );
+        }
+    }
+
+    #[test]
+    fn test_new_with_transfer_encoding() {
+        let request = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n";
+        let err = Request::new(request).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<RequestParseError>(),
+            Some(&RequestParseError::UnsupportedTransferEncoding)
+        );
+
+        let request =
+            b"POST / HTTP/1.1\r\nContent-Length: 3\r\nTransfer-Encoding: chunked\r\n\r\nabc";
+        let err = Request::new(request).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<RequestParseError>(),
+            Some(&RequestParseError::InvalidContentLength)
+        );
+    }
+
+    #[test]
+    fn test_new_with_short_body() {
+        let request = b"GET / HTTP/1.1\r\nContent-Length: 10\r\n\r\nabc";
+        let err = Request::new(request).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RequestParseError>(),
+            Some(RequestParseError::IncompleteBody)
+        ));
+    }
+
+    #[test]
+    fn test_new_with_invalid_content_length() {
+        let request = b"GET / HTTP/1.1\r\nContent-Length: -1\r\n\r\n";
+        let err = Request::new(request).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RequestParseError>(),
+            Some(RequestParseError::InvalidContentLength)
+        ));
     }
 }
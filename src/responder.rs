@@ -1,19 +1,88 @@
-use crate::response::Response;
+use crate::headers::HeaderName;
+use crate::mime;
+use crate::response::{Body, Response};
 use crate::status::Status;
 use std::fs::File;
-use std::io::{self, Read};
+use std::io;
+use std::path::{Path, PathBuf};
 
-/// Trait to convert `String` or `File` into `Response`.
+/// Trait to convert `File` or a path of a file into `Response`.
 pub trait Responder {
     fn to_response(self) -> io::Result<Response>;
 }
 
+/// The content of the file is streamed as bytes, so binary files are also served.
+/// Because the type of the file is unknown, `Content-Type` is `application/octet-stream`.
 impl Responder for File {
-    fn to_response(mut self) -> io::Result<Response> {
-        let mut response = Response::new(Status::OK);
-        let mut file_content = String::new();
-        self.read_to_string(&mut file_content)?;
-        response.set_body(file_content);
-        Ok(response)
+    fn to_response(self) -> io::Result<Response> {
+        file_response(self, mime::OCTET_STREAM)
+    }
+}
+
+/// Open the file and respond with `Content-Type` inferred from the extension.
+impl Responder for &Path {
+    fn to_response(self) -> io::Result<Response> {
+        let file = File::open(self)?;
+        file_response(file, mime::from_path(self))
+    }
+}
+
+impl Responder for PathBuf {
+    fn to_response(self) -> io::Result<Response> {
+        self.as_path().to_response()
+    }
+}
+
+fn file_response(file: File, content_type: &str) -> io::Result<Response> {
+    let metadata = file.metadata()?;
+    if metadata.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Directory cannot be a response",
+        ));
+    }
+    let mut response = Response::new(Status::OK);
+    response
+        .headers
+        .insert(HeaderName::CONTENT_TYPE, content_type.to_string());
+    response.set_body(Body::from_reader(file, metadata.len()));
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::headers::HeaderName;
+    use crate::responder::Responder;
+    use std::fs::{self, File};
+
+    #[test]
+    fn test_binary_file() {
+        let dir =
+            std::env::temp_dir().join(format!("toy_http_server_responder_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("image.png");
+        let content = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0xff, 0x00];
+        fs::write(&path, &content).unwrap();
+
+        let response = path.as_path().to_response().unwrap();
+        assert_eq!(
+            response.headers.get(&HeaderName::CONTENT_TYPE),
+            Some("image/png")
+        );
+        assert_eq!(
+            response.headers.get(&HeaderName::CONTENT_LENGTH),
+            Some("10")
+        );
+        assert_eq!(response.body.into_bytes().unwrap(), content);
+
+        let response = File::open(&path).unwrap().to_response().unwrap();
+        assert_eq!(
+            response.headers.get(&HeaderName::CONTENT_TYPE),
+            Some("application/octet-stream")
+        );
+        assert_eq!(response.body.into_bytes().unwrap(), content);
+
+        assert!(dir.as_path().to_response().is_err());
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -1,13 +1,97 @@
-use crate::headers::{to_vec, HeaderField, HeaderMap};
+use crate::headers::{to_vec, HeaderMap, HeaderName};
 use crate::status::Status;
-use std::collections::HashMap;
-use std::convert::Into;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
 
-#[derive(Clone, Debug)]
+/// Body of a response. A reader is streamed to the client without loading it into memory,
+/// so its length must be known beforehand.
+#[derive(Default)]
+pub enum Body {
+    #[default]
+    Empty,
+    Bytes(Vec<u8>),
+    Reader(Box<dyn Read + Send>, u64),
+}
+
+impl Body {
+    pub fn from_reader<R: Read + Send + 'static>(reader: R, length: u64) -> Self {
+        Body::Reader(Box::new(reader), length)
+    }
+
+    pub fn len(&self) -> u64 {
+        match self {
+            Body::Empty => 0,
+            Body::Bytes(bytes) => bytes.len() as u64,
+            Body::Reader(_, length) => *length,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return bytes of the body, or `None` if the body is a reader.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Body::Empty => Some(&[]),
+            Body::Bytes(bytes) => Some(bytes),
+            Body::Reader(_, _) => None,
+        }
+    }
+
+    /// Read whole body into memory.
+    pub fn into_bytes(self) -> io::Result<Vec<u8>> {
+        match self {
+            Body::Empty => Ok(Vec::new()),
+            Body::Bytes(bytes) => Ok(bytes),
+            Body::Reader(reader, length) => {
+                let mut bytes = Vec::new();
+                reader.take(length).read_to_end(&mut bytes)?;
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Body::Empty => write!(f, "Empty"),
+            Body::Bytes(bytes) => write!(f, "Bytes({} bytes)", bytes.len()),
+            Body::Reader(_, length) => write!(f, "Reader({} bytes)", length),
+        }
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(bytes: Vec<u8>) -> Self {
+        Body::Bytes(bytes)
+    }
+}
+
+impl From<&[u8]> for Body {
+    fn from(bytes: &[u8]) -> Self {
+        Body::Bytes(bytes.to_vec())
+    }
+}
+
+impl From<String> for Body {
+    fn from(body: String) -> Self {
+        Body::Bytes(body.into_bytes())
+    }
+}
+
+impl From<&str> for Body {
+    fn from(body: &str) -> Self {
+        Body::Bytes(body.as_bytes().to_vec())
+    }
+}
+
+#[derive(Debug)]
 pub struct Response {
-    pub status_code: u16,
-    pub reason_phrase: String,
-    pub body: Option<Vec<u8>>,
+    pub status: Status,
+    pub body: Body,
     pub headers: HeaderMap,
 }
 
@@ -16,39 +100,306 @@ impl Response {
     /// Construct new `Response` from status code.
     /// Headers and body is ramained empty.
     pub fn new(status: Status) -> Self {
-        let (status_code, reason_phrase) = status.into();
         Self {
-            status_code,
-            reason_phrase,
-            body: None,
-            headers: HashMap::new(),
+            status,
+            body: Body::Empty,
+            headers: HeaderMap::new(),
         }
     }
 
-    /// Set response body and correspondeing headers.
-    // Todo: make it possible to specify headers with arguments.
-    pub fn set_body(&mut self, body: String) {
-        let body = body.into_bytes();
-        let length = body.len();
-        self.headers
-            .insert(HeaderField::ContentLength, length.to_string());
+    /// Start building a response, whose status is 200 OK unless specified.
+    pub fn builder() -> ResponseBuilder {
+        ResponseBuilder::new()
+    }
+
+    /// Set response body and `Content-Length` header.
+    pub fn set_body<B: Into<Body>>(&mut self, body: B) {
+        let body = body.into();
         self.headers
-            .insert(HeaderField::ContentType, "text/html".to_string());
-        self.body = Some(body);
+            .insert(HeaderName::CONTENT_LENGTH, body.len().to_string());
+        self.body = body;
     }
+
+    /// Drop the body to respond to a HEAD request. `Content-Length` header is kept as the
+    /// length of the body which would be sent to GET.
+    pub(crate) fn omit_body(&mut self) {
+        self.set_content_length();
+        self.body = Body::Empty;
+    }
+
+    /// Add `Content-Length` header of the body if it is not set. Responses with 1xx and 204
+    /// have neither the header nor a body as described in RFC 7230 section 3.3.2.
+    pub(crate) fn set_content_length(&mut self) {
+        if self.status.is_informational() || self.status == Status::NoContent {
+            self.headers.remove(&HeaderName::CONTENT_LENGTH);
+            self.body = Body::Empty;
+            return;
+        }
+        // `Content-Length` of 304 would mean the length of the unmodified representation.
+        if self.status != Status::NotModified
+            && !self.headers.contains_key(&HeaderName::CONTENT_LENGTH)
+        {
+            self.headers
+                .insert(HeaderName::CONTENT_LENGTH, self.body.len().to_string());
+        }
+    }
+
+    /// Write the status line, headers and body. `Content-Length` header is added if it is
+    /// not set and the status allows it.
+    pub fn write_to<W: Write>(mut self, writer: &mut W) -> io::Result<()> {
+        self.set_content_length();
+
+        let mut bytes = Vec::new();
+        let status_line = format!("HTTP/1.1 {}\r\n", self.status);
+        bytes.append(&mut status_line.into_bytes());
+        bytes.append(&mut to_vec(&self.headers));
+        bytes.append(&mut "\r\n".as_bytes().to_vec());
+        match self.body {
+            Body::Empty => writer.write_all(&bytes)?,
+            Body::Bytes(mut body) => {
+                bytes.append(&mut body);
+                writer.write_all(&bytes)?;
+            }
+            Body::Reader(reader, length) => {
+                writer.write_all(&bytes)?;
+                let copied = io::copy(&mut reader.take(length), writer)?;
+                if copied < length {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "Body is shorter than its length",
+                    ));
+                }
+            }
+        }
+        writer.flush()
+    }
+}
+
+/// Builder of `Response`. Errors such as invalid header values are reported when the
+/// response is built by `body` or `build`.
+#[derive(Debug)]
+pub struct ResponseBuilder {
+    response: Response,
+    error: Option<ResponseBuildError>,
 }
 
-impl Into<Vec<u8>> for Response {
-    fn into(self) -> Vec<u8> {
-        // Consider to implement `fmt::Display` for `Status`.
-        let mut response = Vec::new();
-        let status_line = format!("HTTP/1.1 {} {}\r\n", self.status_code, self.reason_phrase);
-        response.append(&mut status_line.into_bytes());
-        response.append(&mut to_vec(&self.headers));
-        response.append(&mut "\r\n".as_bytes().to_vec());
-        if let Some(mut body) = self.body {
-            response.append(&mut body);
+impl ResponseBuilder {
+    pub fn new() -> Self {
+        Self {
+            response: Response::new(Status::OK),
+            error: None,
         }
-        response
+    }
+
+    pub fn status(mut self, status: Status) -> Self {
+        self.response.status = status;
+        self
+    }
+
+    /// Set the header, replacing existing values of it. The value must not contain CR or LF,
+    /// which would let the value inject other headers into the response.
+    pub fn header<V: Into<String>>(mut self, header_name: HeaderName, value: V) -> Self {
+        if let Some(value) = self.validate(&header_name, value.into()) {
+            self.response.headers.insert(header_name, value);
+        }
+        self
+    }
+
+    /// Add a value of the header such as `Set-Cookie` after existing ones.
+    pub fn append_header<V: Into<String>>(mut self, header_name: HeaderName, value: V) -> Self {
+        if let Some(value) = self.validate(&header_name, value.into()) {
+            self.response.headers.append(header_name, value);
+        }
+        self
+    }
+
+    fn validate(&mut self, header_name: &HeaderName, value: String) -> Option<String> {
+        if value.contains(['\r', '\n', '\0']) {
+            self.error
+                .get_or_insert(ResponseBuildError::InvalidHeaderValue(header_name.clone()));
+            None
+        } else {
+            Some(value)
+        }
+    }
+
+    pub fn content_type(self, content_type: &str) -> Self {
+        self.header(HeaderName::CONTENT_TYPE, content_type)
+    }
+
+    /// Build the response with the body. `Content-Length` is set to the length of the body.
+    pub fn body<B: Into<Body>>(mut self, body: B) -> Result<Response, ResponseBuildError> {
+        self.response.set_body(body);
+        self.build()
+    }
+
+    /// Build the response without body.
+    pub fn build(self) -> Result<Response, ResponseBuildError> {
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(self.response),
+        }
+    }
+}
+
+impl Default for ResponseBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ResponseBuildError {
+    InvalidHeaderValue(HeaderName),
+}
+
+impl fmt::Display for ResponseBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResponseBuildError::InvalidHeaderValue(header_name) => {
+                let header_name: Vec<u8> = header_name.into();
+                write!(
+                    f,
+                    "Invalid value for {}",
+                    String::from_utf8_lossy(&header_name)
+                )
+            }
+        }
+    }
+}
+
+impl Error for ResponseBuildError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::headers::HeaderName;
+    use crate::response::{Body, Response, ResponseBuildError};
+    use crate::status::Status;
+
+    fn to_string(response: Response) -> String {
+        let mut bytes = Vec::new();
+        response.write_to(&mut bytes).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn test_builder() {
+        let response = Response::builder()
+            .status(Status::NotFound)
+            .content_type("application/json")
+            .body(r#"{"error":"not found"}"#)
+            .unwrap();
+        assert_eq!(response.status, Status::NotFound);
+        assert_eq!(
+            response.headers.get(&HeaderName::CONTENT_TYPE),
+            Some("application/json")
+        );
+        assert_eq!(
+            response.headers.get(&HeaderName::CONTENT_LENGTH),
+            Some("21")
+        );
+        assert_eq!(
+            response.body.as_bytes(),
+            Some(&br#"{"error":"not found"}"#[..])
+        );
+    }
+
+    #[test]
+    fn test_builder_with_bodies() {
+        let bodies: Vec<Body> = vec![
+            vec![0xff, 0x00].into(),
+            (&b"ab"[..]).into(),
+            "ab".into(),
+            "ab".to_string().into(),
+            Body::from_reader(&b"abc"[..], 2),
+        ];
+        for body in bodies {
+            let response = Response::builder().body(body).unwrap();
+            assert_eq!(response.headers.get(&HeaderName::CONTENT_LENGTH), Some("2"));
+            assert_eq!(response.body.into_bytes().unwrap().len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_builder_rejects_line_breaks() {
+        let result = Response::builder()
+            .header(HeaderName::CONTENT_TYPE, "text/html\r\nSet-Cookie: a=b")
+            .body("");
+        assert!(matches!(
+            result,
+            Err(ResponseBuildError::InvalidHeaderValue(name)) if name == HeaderName::CONTENT_TYPE
+        ));
+        let result = Response::builder().content_type("text/html\n").build();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_write_to() {
+        let response = Response::builder()
+            .status(Status::Created)
+            .body(Body::from_reader(&b"hello world"[..], 5))
+            .unwrap();
+        assert_eq!(
+            to_string(response),
+            "HTTP/1.1 201 Created\r\nContent-Length: 5\r\n\r\nhello"
+        );
+    }
+
+    #[test]
+    fn test_write_to_without_body() {
+        let response = Response::new(Status::NoContent);
+        assert_eq!(to_string(response), "HTTP/1.1 204 No Content\r\n\r\n");
+        let response = Response::new(Status::OK);
+        assert_eq!(
+            to_string(response),
+            "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_write_to_without_content_length() {
+        let mut response = Response::new(Status::Continue);
+        response.set_body("ignored");
+        assert_eq!(to_string(response), "HTTP/1.1 100 Continue\r\n\r\n");
+        let response = Response::new(Status::NotModified);
+        assert_eq!(to_string(response), "HTTP/1.1 304 Not Modified\r\n\r\n");
+    }
+
+    #[test]
+    fn test_write_to_keeps_header_order() {
+        let response = Response::builder()
+            .header(HeaderName::CONTENT_TYPE, "text/plain")
+            .append_header(HeaderName::SET_COOKIE, "a=1")
+            .header(HeaderName::VARY, "Accept")
+            .append_header(HeaderName::SET_COOKIE, "b=2")
+            .body("ok")
+            .unwrap();
+        assert_eq!(
+            to_string(response),
+            concat!(
+                "HTTP/1.1 200 OK\r\n",
+                "Content-Type: text/plain\r\n",
+                "Set-Cookie: a=1\r\n",
+                "Vary: Accept\r\n",
+                "Set-Cookie: b=2\r\n",
+                "Content-Length: 2\r\n",
+                "\r\n",
+                "ok"
+            )
+        );
+    }
+
+    #[test]
+    fn test_write_to_not_modified() {
+        let response = Response::new(Status::NotModified);
+        assert_eq!(to_string(response), "HTTP/1.1 304 Not Modified\r\n\r\n");
+    }
+
+    #[test]
+    fn test_write_to_with_short_reader() {
+        let response = Response::builder()
+            .body(Body::from_reader(&b"abc"[..], 5))
+            .unwrap();
+        assert!(response.write_to(&mut Vec::new()).is_err());
     }
 }
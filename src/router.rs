@@ -1,17 +1,136 @@
 use crate::handler::Handler;
+use crate::request::Method;
+use std::collections::HashMap;
+
+/// Handlers registered for a path. Each handler is bound to a method, or to every method
+/// if the method is `None`.
+#[derive(Debug, Default)]
+pub struct Endpoint {
+    handlers: Vec<(Option<Method>, Box<dyn Handler>)>,
+}
+
+impl Endpoint {
+    fn insert(&mut self, method: Option<Method>, handler: Box<dyn Handler>) {
+        match self.handlers.iter_mut().find(|(m, _)| *m == method) {
+            Some(registered) => registered.1 = handler,
+            None => self.handlers.push((method, handler)),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+
+    fn find(&self, method: &Method) -> Option<&dyn Handler> {
+        self.handlers
+            .iter()
+            .find(|(m, _)| m.as_ref() == Some(method))
+            .map(|(_, handler)| handler.as_ref())
+    }
+
+    /// Return a handler for the method. A handler bound to the method takes precedence
+    /// over one bound to every method. HEAD falls back to the GET handler, since the
+    /// server omits the body of responses to HEAD.
+    pub fn handler(&self, method: &Method) -> Option<&dyn Handler> {
+        self.find(method)
+            .or_else(|| match method {
+                Method::Head => self.find(&Method::Get),
+                _ => None,
+            })
+            .or_else(|| {
+                self.handlers
+                    .iter()
+                    .find(|(m, _)| m.is_none())
+                    .map(|(_, handler)| handler.as_ref())
+            })
+    }
+
+    /// Return methods which have handlers in order of registration. HEAD follows GET
+    /// unless it has its own handler.
+    pub fn allowed_methods(&self) -> Vec<Method> {
+        let mut methods = Vec::new();
+        for method in self
+            .handlers
+            .iter()
+            .filter_map(|(method, _)| method.as_ref())
+        {
+            methods.push(method.clone());
+            if *method == Method::Get && self.find(&Method::Head).is_none() {
+                methods.push(Method::Head);
+            }
+        }
+        methods
+    }
+}
+
+/// Kind of a node in the tree.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+enum NodeKind {
+    /// Matches the path of the node literally.
+    #[default]
+    Static,
+    /// Matches one path segment, such as `:id`, and captures it.
+    Param,
+    /// Matches the rest of the path, such as `*` or `*path`, and captures it.
+    CatchAll,
+}
+
+/// A piece of a registered path.
+enum Segment<'a> {
+    Static(&'a str),
+    Param(&'a str),
+    CatchAll(&'a str),
+}
+
+/// Split a registered path into static parts, parameters and a wildcard.
+/// For example, "/users/:id/posts" is split into "/users/", ":id" and "/posts".
+fn split_segments(path: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut static_start = 0;
+    let mut pos = 0;
+    while pos < path.len() {
+        let at_segment_start = pos == 0 || path.as_bytes()[pos - 1] == b'/';
+        match path.as_bytes()[pos] {
+            b':' if at_segment_start => {
+                if static_start < pos {
+                    segments.push(Segment::Static(&path[static_start..pos]));
+                }
+                let end = path[pos..].find('/').map_or(path.len(), |end| pos + end);
+                if end == pos + 1 {
+                    panic!("Parameter must be named: {}", path);
+                }
+                segments.push(Segment::Param(&path[pos..end]));
+                static_start = end;
+                pos = end;
+            }
+            b'*' if at_segment_start => {
+                if static_start < pos {
+                    segments.push(Segment::Static(&path[static_start..pos]));
+                }
+                if path[pos..].contains('/') {
+                    panic!("Wildcard must be at the end of the path: {}", path);
+                }
+                segments.push(Segment::CatchAll(&path[pos..]));
+                static_start = path.len();
+                pos = path.len();
+            }
+            _ => pos += 1,
+        }
+    }
+    if static_start < path.len() {
+        segments.push(Segment::Static(&path[static_start..]));
+    }
+    segments
+}
 
 /// URI paths are represented as trie tree.
 /// This struct is a node of the tree.
 #[derive(Debug, Default)]
 pub struct Router {
     pub path: String,
-    pub handler: Option<Box<dyn Handler>>,
-    children: Vec<Box<Router>>,
-}
-
-/// Check if the path has wild card at the end of the path.
-fn includes_wildcard(path: &str) -> bool {
-    path.ends_with("/*")
+    kind: NodeKind,
+    pub endpoint: Endpoint,
+    children: Vec<Router>,
 }
 
 impl Router {
@@ -19,24 +138,22 @@ impl Router {
         Default::default()
     }
 
-    fn new_child<F: Handler>(path: &str, handler: F) -> Self {
-        let mut child = Self {
+    fn new_child(path: &str, kind: NodeKind) -> Self {
+        Self {
             path: path.to_string(),
-            handler: Some(Box::new(handler)),
+            kind,
+            endpoint: Endpoint::default(),
             children: Vec::new(),
-        };
-        if includes_wildcard(path) && !path.starts_with('*') {
-            child.split_wildcard();
         }
-        child
     }
 
-    /// Return how many common character path of `Route` nodes and an arugument have.
+    /// Return how many common bytes path of `Route` nodes and an arugument have.
+    /// The result is always on a character boundary of both strings.
     fn longest_common_prefix(&self, other: &str) -> usize {
         let mut pos = 0;
         for (char_self, char_other) in self.path.chars().zip(other.chars()) {
             if char_self == char_other {
-                pos += 1;
+                pos += char_self.len_utf8();
             } else {
                 break;
             }
@@ -44,128 +161,186 @@ impl Router {
         pos
     }
 
+    /// Register a handler which serves every method on the path.
     pub fn add_route<F: Handler>(&mut self, new_path: &str, handler: F) {
-        // For the first time to insert node to root.
-        if self.path.len() == 0 && self.children.len() == 0 {
-            self.children
-                .push(Box::new(Router::new_child(new_path, handler)));
-            return;
-        }
-        if self.path == new_path {
-            self.handler = Some(Box::new(handler));
-            return;
+        self.insert(&split_segments(new_path), None, Box::new(handler));
+    }
+
+    /// Register a handler which serves only the method on the path.
+    pub fn add_method_route<F: Handler>(&mut self, method: Method, new_path: &str, handler: F) {
+        self.insert(&split_segments(new_path), Some(method), Box::new(handler));
+    }
+
+    /// Insert segments below this node. The path of this node is already consumed.
+    fn insert(&mut self, segments: &[Segment], method: Option<Method>, handler: Box<dyn Handler>) {
+        match segments.split_first() {
+            None => self.endpoint.insert(method, handler),
+            Some((Segment::Static(path), remaining)) => {
+                self.insert_static(path, remaining, method, handler)
+            }
+            Some((Segment::Param(name), remaining)) => {
+                self.dynamic_child(name, NodeKind::Param)
+                    .insert(remaining, method, handler);
+            }
+            Some((Segment::CatchAll(name), _)) => {
+                self.dynamic_child(name, NodeKind::CatchAll)
+                    .endpoint
+                    .insert(method, handler);
+            }
         }
+    }
 
-        let lcp = self.longest_common_prefix(new_path);
-        let path = self.path.clone();
-        if path.len() > lcp {
-            // For example, `self.path` is "static" and longest common prefix is "stat".
-            let common_prefix = &path[..lcp];
-            let path_remaining = &path[lcp..];
-            let new_path_remaining = &new_path[lcp..];
-
-            self.path = common_prefix.to_string();
-            let deriving_child = Self {
-                path: path_remaining.to_string(),
-                handler: std::mem::take(&mut self.handler),
-                children: std::mem::take(&mut self.children),
-            };
-            if new_path_remaining.len() > 0 {
+    fn insert_static(
+        &mut self,
+        new_path: &str,
+        segments: &[Segment],
+        method: Option<Method>,
+        handler: Box<dyn Handler>,
+    ) {
+        for child in &mut self.children {
+            // Because more than 2 static children do not have same prefix,
+            // just check first character of key for each child.
+            if child.kind != NodeKind::Static
+                || child.path.chars().next() != new_path.chars().next()
+            {
+                continue;
+            }
+            let lcp = child.longest_common_prefix(new_path);
+            if child.path.len() > lcp {
+                // For example, `child.path` is "static" and longest common prefix is "stat".
+                child.split_at(lcp);
+            }
+            if new_path.len() > lcp {
                 // For example, "abc" and "ade".
-                self.children = vec![
-                    Box::new(deriving_child),
-                    Box::new(Router::new_child(new_path_remaining, handler)),
-                ];
+                child.insert_static(&new_path[lcp..], segments, method, handler);
             } else {
                 // For example, "abc" and "a".
-                // If "a" is inserted in the same way as previous block, a handler for the node "a"
-                // is replaced with `None` but the node has a `handler`.
-                self.handler = Some(Box::new(handler));
-                self.children = vec![Box::new(deriving_child)];
-            }
-        } else {
-            // When longest common prefix is exactly the same as `self.path`.
-            let new_path_remaining = &new_path[lcp..];
-            for child in &mut self.children {
-                match (*child).path.chars().next() {
-                    // Because more than 2 children node do not have same prefix,
-                    // just check first character of key for each child.
-                    Some(first_char)
-                        if first_char == new_path_remaining.chars().next().unwrap() =>
-                    {
-                        child.add_route(new_path_remaining, handler);
-                        return;
-                    }
-                    _ => continue,
-                }
+                child.insert(segments, method, handler);
             }
-            // If there is no child in `self.children` that matches new path, just insert it.
-            self.children
-                .push(Box::new(Router::new_child(new_path_remaining, handler)));
+            return;
         }
+        // If there is no child in `self.children` that matches new path, just insert it.
+        let mut child = Router::new_child(new_path, NodeKind::Static);
+        child.insert(segments, method, handler);
+        self.children.push(child);
     }
 
-    fn split_wildcard(&mut self) {
-        if includes_wildcard(&self.path) {
-            self.path = self.path.trim_end_matches('*').to_string();
-            self.children.push(Box::new(Self {
-                path: "*".to_string(),
-                handler: None,
-                children: Vec::new(),
-            }));
-        }
+    /// Split the path of this node at `pos` and move the remaining path into a new child.
+    fn split_at(&mut self, pos: usize) {
+        let deriving_child = Self {
+            path: self.path[pos..].to_string(),
+            kind: NodeKind::Static,
+            endpoint: std::mem::take(&mut self.endpoint),
+            children: std::mem::take(&mut self.children),
+        };
+        self.path.truncate(pos);
+        self.children = vec![deriving_child];
+    }
+
+    /// Return the child for a parameter or a wildcard, creating it if it does not exist.
+    /// A node has at most one parameter child, so parameters with different names at the
+    /// same position conflict.
+    fn dynamic_child(&mut self, name: &str, kind: NodeKind) -> &mut Router {
+        let position = self.children.iter().position(|child| child.kind == kind);
+        let position = match position {
+            Some(position) if self.children[position].path == name => position,
+            Some(position) => panic!(
+                "'{}' conflicts with existing '{}'",
+                name, self.children[position].path
+            ),
+            None => {
+                self.children.push(Router::new_child(name, kind));
+                self.children.len() - 1
+            }
+        };
+        &mut self.children[position]
     }
 
-    pub fn find(&self, key: &str) -> Option<&Box<dyn Handler>> {
-        if key.len() == 0 {
+    fn child(&self, kind: NodeKind) -> Option<&Router> {
+        self.children.iter().find(|child| child.kind == kind)
+    }
+
+    /// Find handlers registered for the path and values of parameters in the path.
+    /// Static paths take precedence over parameters, and parameters take precedence over
+    /// wildcards.
+    pub fn find(&self, key: &str) -> Option<(&Endpoint, HashMap<String, String>)> {
+        if key.is_empty() {
             return None;
         }
-        let lcp = self.longest_common_prefix(key);
-        let key_remaining = &key[lcp..];
-        if key_remaining.len() == 0 {
-            return self.handler.as_ref();
+        let mut params = Vec::new();
+        let endpoint = self.lookup(key, &mut params)?;
+        Some((endpoint, params.into_iter().collect()))
+    }
+
+    /// Find handlers below this node. The path of this node is already consumed.
+    fn lookup<'a>(&'a self, key: &str, params: &mut Vec<(String, String)>) -> Option<&'a Endpoint> {
+        if key.is_empty() {
+            if !self.endpoint.is_empty() {
+                return Some(&self.endpoint);
+            }
+            return self.lookup_catch_all(key, params);
         }
 
-        for child in &self.children {
-            if &child.path == "*" {
-                return self.handler.as_ref();
+        let static_child = self
+            .children
+            .iter()
+            .find(|child| child.kind == NodeKind::Static && key.starts_with(child.path.as_str()));
+        if let Some(child) = static_child {
+            if let Some(endpoint) = child.lookup(&key[child.path.len()..], params) {
+                return Some(endpoint);
             }
-            match (*child).path.chars().next() {
-                // Because more than 2 children node do not have same prefix,
-                // just check first character of key for each child.
-                Some(first_char) if first_char == key_remaining.chars().next().unwrap() => {
-                    return child.find(key_remaining);
+        }
+
+        if let Some(child) = self.child(NodeKind::Param) {
+            let end = key.find('/').unwrap_or(key.len());
+            if end > 0 {
+                let name = child.path.trim_start_matches(':').to_string();
+                params.push((name, key[..end].to_string()));
+                if let Some(endpoint) = child.lookup(&key[end..], params) {
+                    return Some(endpoint);
                 }
-                _ => continue,
+                params.pop();
             }
         }
-        None
+
+        self.lookup_catch_all(key, params)
+    }
+
+    /// Match the rest of the path with a wildcard child. The rest is captured as `*`, and
+    /// also as the name of the wildcard if it is named like `*path`.
+    fn lookup_catch_all<'a>(
+        &'a self,
+        key: &str,
+        params: &mut Vec<(String, String)>,
+    ) -> Option<&'a Endpoint> {
+        let child = self.child(NodeKind::CatchAll)?;
+        if child.endpoint.is_empty() {
+            return None;
+        }
+        let name = child.path.trim_start_matches('*');
+        if !name.is_empty() {
+            params.push((name.to_string(), key.to_string()));
+        }
+        params.push(("*".to_string(), key.to_string()));
+        Some(&child.endpoint)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::request::Request;
+    use crate::request::{Method, Request};
     use crate::response::Response;
-    use crate::router::Router;
+    use crate::router::{NodeKind, Router};
 
     #[test]
     fn test_lcp() {
-        let node_x = Router {
-            path: "abcde".to_string(),
-            handler: None,
-            children: Vec::new(),
-        };
+        let node_x = Router::new_child("abcde", NodeKind::Static);
         assert_eq!(node_x.longest_common_prefix("abchoge"), 3);
     }
 
     #[test]
     fn test_lcp_root() {
-        let node_x = Router {
-            path: "".to_string(),
-            handler: None,
-            children: Vec::new(),
-        };
+        let node_x = Router::new();
         assert_eq!(node_x.longest_common_prefix("abchoge"), 0);
     }
 
@@ -238,4 +413,207 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_find_with_method() {
+        let mut tree = Router::new();
+        tree.add_method_route(Method::Get, "/users", dummy_handler);
+        tree.add_method_route(Method::Post, "/users", dummy_handler);
+        tree.add_method_route(Method::Delete, "/users/1", dummy_handler);
+
+        let (endpoint, _) = tree.find("/users").unwrap();
+        assert!(endpoint.handler(&Method::Get).is_some());
+        assert!(endpoint.handler(&Method::Post).is_some());
+        assert!(endpoint.handler(&Method::Delete).is_none());
+        assert_eq!(
+            endpoint.allowed_methods(),
+            vec![Method::Get, Method::Head, Method::Post]
+        );
+
+        let (endpoint, _) = tree.find("/users/1").unwrap();
+        assert!(endpoint.handler(&Method::Get).is_none());
+        assert_eq!(endpoint.allowed_methods(), vec![Method::Delete]);
+    }
+
+    #[test]
+    fn test_find_with_any_method() {
+        let mut tree = Router::new();
+        tree.add_route("/", dummy_handler);
+        tree.add_method_route(Method::Post, "/", dummy_handler);
+        let (endpoint, _) = tree.find("/").unwrap();
+        assert!(endpoint.handler(&Method::Get).is_some());
+        assert!(endpoint
+            .handler(&Method::Extension("PROPFIND".to_string()))
+            .is_some());
+        assert_eq!(endpoint.allowed_methods(), vec![Method::Post]);
+    }
+
+    #[test]
+    fn test_find_head_with_get() {
+        let mut tree = Router::new();
+        tree.add_method_route(Method::Get, "/get", dummy_handler);
+        tree.add_method_route(Method::Post, "/post", dummy_handler);
+        tree.add_method_route(Method::Head, "/both", dummy_handler);
+        tree.add_method_route(Method::Get, "/both", dummy_handler);
+
+        let (endpoint, _) = tree.find("/get").unwrap();
+        assert!(endpoint.handler(&Method::Head).is_some());
+        assert_eq!(endpoint.allowed_methods(), vec![Method::Get, Method::Head]);
+        let (endpoint, _) = tree.find("/post").unwrap();
+        assert!(endpoint.handler(&Method::Head).is_none());
+        assert_eq!(endpoint.allowed_methods(), vec![Method::Post]);
+        let (endpoint, _) = tree.find("/both").unwrap();
+        assert_eq!(endpoint.allowed_methods(), vec![Method::Head, Method::Get]);
+    }
+
+    #[test]
+    fn test_find_without_handler() {
+        let mut tree = Router::new();
+        tree.add_route("/static", dummy_handler);
+        tree.add_route("/style", dummy_handler);
+        // "/st" is a node of the tree but no handler is registered.
+        assert!(tree.find("/st").is_none());
+    }
+
+    fn params(tree: &Router, key: &str) -> Option<Vec<(String, String)>> {
+        let (_, params) = tree.find(key)?;
+        let mut params = params.into_iter().collect::<Vec<(String, String)>>();
+        params.sort();
+        Some(params)
+    }
+
+    fn param(name: &str, value: &str) -> (String, String) {
+        (name.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn test_find_with_params() {
+        let mut tree = Router::new();
+        tree.add_route("/users/:id", dummy_handler);
+        tree.add_route("/users/:id/posts/:post_id", dummy_handler);
+        assert_eq!(params(&tree, "/users/42"), Some(vec![param("id", "42")]));
+        assert_eq!(
+            params(&tree, "/users/42/posts/7"),
+            Some(vec![param("id", "42"), param("post_id", "7")])
+        );
+        assert_eq!(params(&tree, "/users/"), None);
+        assert_eq!(params(&tree, "/users/42/posts"), None);
+        assert_eq!(params(&tree, "/users/42/posts/7/comments"), None);
+    }
+
+    #[test]
+    fn test_find_static_over_param() {
+        let mut tree = Router::new();
+        tree.add_route("/users/:id", dummy_handler);
+        tree.add_route("/users/new", dummy_handler);
+        tree.add_route("/users/:id/posts", dummy_handler);
+        assert_eq!(params(&tree, "/users/new"), Some(vec![]));
+        // "newer" shares a prefix with "new" but is not the static path.
+        assert_eq!(
+            params(&tree, "/users/newer"),
+            Some(vec![param("id", "newer")])
+        );
+        // "/users/new/posts" is not registered as a static path.
+        assert_eq!(
+            params(&tree, "/users/new/posts"),
+            Some(vec![param("id", "new")])
+        );
+    }
+
+    #[test]
+    fn test_find_param_over_wildcard() {
+        let mut tree = Router::new();
+        tree.add_route("/files/*", dummy_handler);
+        tree.add_route("/files/:name", dummy_handler);
+        tree.add_route("/files/index.html", dummy_handler);
+        assert_eq!(params(&tree, "/files/index.html"), Some(vec![]));
+        assert_eq!(
+            params(&tree, "/files/a.txt"),
+            Some(vec![param("name", "a.txt")])
+        );
+        assert_eq!(
+            params(&tree, "/files/a/b.txt"),
+            Some(vec![param("*", "a/b.txt")])
+        );
+        assert_eq!(params(&tree, "/files/"), Some(vec![param("*", "")]));
+    }
+
+    #[test]
+    fn test_find_params_in_registration_order_independent() {
+        let mut tree = Router::new();
+        tree.add_route("/users/new", dummy_handler);
+        tree.add_route("/users/:id", dummy_handler);
+        tree.add_route("/users", dummy_handler);
+        tree.add_route("/u", dummy_handler);
+        assert_eq!(params(&tree, "/users/new"), Some(vec![]));
+        assert_eq!(params(&tree, "/users/1"), Some(vec![param("id", "1")]));
+        assert_eq!(params(&tree, "/users"), Some(vec![]));
+        assert_eq!(params(&tree, "/u"), Some(vec![]));
+        assert_eq!(params(&tree, "/us"), None);
+    }
+
+    #[test]
+    fn test_add_same_param_twice() {
+        let mut tree = Router::new();
+        tree.add_method_route(Method::Get, "/users/:id", dummy_handler);
+        tree.add_method_route(Method::Put, "/users/:id", dummy_handler);
+        let (endpoint, _) = tree.find("/users/1").unwrap();
+        assert_eq!(
+            endpoint.allowed_methods(),
+            vec![Method::Get, Method::Head, Method::Put]
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_add_conflicting_params() {
+        let mut tree = Router::new();
+        tree.add_route("/users/:id", dummy_handler);
+        tree.add_route("/users/:name/posts", dummy_handler);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_add_unnamed_param() {
+        let mut tree = Router::new();
+        tree.add_route("/users/:/posts", dummy_handler);
+    }
+
+    #[test]
+    fn test_find_with_named_wildcard() {
+        let mut tree = Router::new();
+        tree.add_route("/static/*path", dummy_handler);
+        tree.add_route("/users/:id/files/*path", dummy_handler);
+        assert_eq!(
+            params(&tree, "/static/css/style.css"),
+            Some(vec![
+                param("*", "css/style.css"),
+                param("path", "css/style.css")
+            ])
+        );
+        assert_eq!(
+            params(&tree, "/users/1/files/a.txt"),
+            Some(vec![
+                param("*", "a.txt"),
+                param("id", "1"),
+                param("path", "a.txt")
+            ])
+        );
+        assert_eq!(params(&tree, "/static"), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_add_conflicting_wildcards() {
+        let mut tree = Router::new();
+        tree.add_route("/static/*path", dummy_handler);
+        tree.add_route("/static/*file", dummy_handler);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_add_wildcard_in_middle() {
+        let mut tree = Router::new();
+        tree.add_route("/static/*path/index.html", dummy_handler);
+    }
 }
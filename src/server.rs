@@ -1,15 +1,149 @@
+use crate::connection::Connection;
 use crate::handler::Handler;
-use crate::request::Request;
+use crate::headers::HeaderName;
+use crate::request::{Method, Request, RequestParseError, Version};
+use crate::response::Response;
 use crate::router::Router;
+use crate::status::Status;
+use crate::thread_pool::ThreadPool;
+use std::collections::HashMap;
 use std::error::Error;
-use std::io::{self, Read, Write};
+use std::io;
 use std::net;
-use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 pub struct Server {
     address: String,
     port: u16,
     router: Router,
+    not_found: Box<dyn Handler>,
+    workers: usize,
+    keep_alive_timeout: Duration,
+    max_requests: usize,
+    max_header_size: usize,
+    max_body_size: usize,
+    shared: Arc<Shared>,
+    shutdown_timeout: Duration,
+    /// Number of accepted connections waiting for a worker thread.
+    queued_connections: AtomicUsize,
+    /// Number of connections handled by worker threads.
+    active_connections: AtomicUsize,
+    /// Source of keys of `Shared::idle_connections`.
+    next_idle_id: AtomicUsize,
+}
+
+/// State shared between `Server` and `ShutdownHandle`.
+#[derive(Debug, Default)]
+struct Shared {
+    shutdown: AtomicBool,
+    /// Address which is connected to wake up the thread blocked in `accept` on shutdown.
+    wake_addr: Mutex<Option<net::SocketAddr>>,
+    /// Clones of connections waiting for their next request. Shutting one down makes its
+    /// worker stop waiting.
+    idle_connections: Mutex<HashMap<usize, IdleConnection>>,
+}
+
+#[derive(Debug)]
+struct IdleConnection {
+    stream: net::TcpStream,
+    /// Whether the connection has served requests.
+    has_served: bool,
+}
+
+/// Handle to stop a running `Server` from another thread.
+#[derive(Clone, Debug)]
+pub struct ShutdownHandle {
+    shared: Arc<Shared>,
+}
+
+impl ShutdownHandle {
+    /// Make the server stop accepting new connections. `Server::run` returns after
+    /// requests in progress are finished or the shutdown timeout expires.
+    pub fn shutdown(&self) {
+        self.shared.shutdown.store(true, Ordering::SeqCst);
+        for idle in self.shared.idle_connections.lock().unwrap().values() {
+            let _ = idle.stream.shutdown(net::Shutdown::Both);
+        }
+        if let Some(addr) = *self.shared.wake_addr.lock().unwrap() {
+            let _ = net::TcpStream::connect(addr);
+        }
+    }
+}
+
+/// Keeps a connection counted in `Server::active_connections` until it is dropped,
+/// even if a handler panics.
+struct ActiveConnection<'a>(&'a AtomicUsize);
+
+impl<'a> ActiveConnection<'a> {
+    fn new(count: &'a AtomicUsize) -> Self {
+        count.fetch_add(1, Ordering::SeqCst);
+        Self(count)
+    }
+}
+
+impl Drop for ActiveConnection<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Server bound to an address, which is created by `Server::listen`.
+pub struct RunningServer {
+    server: Server,
+    listener: net::TcpListener,
+}
+
+impl RunningServer {
+    /// Return the address the server is bound to.
+    pub fn local_addr(&self) -> io::Result<net::SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Return a handle to stop the server after `run` is called.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.server.shutdown_handle()
+    }
+
+    /// Accept connections and dispatch them to worker threads until shutdown is requested
+    /// through `ShutdownHandle`.
+    pub fn run(self) -> io::Result<()> {
+        println!("Server listening on {}", self.local_addr()?);
+        let pool = ThreadPool::new(self.server.workers);
+        let server = Arc::new(self.server);
+        while !server.is_shutting_down() {
+            let (stream, _) = self.listener.accept()?;
+            // `ShutdownHandle` connects to wake up `accept`.
+            if server.is_shutting_down() {
+                break;
+            }
+            server.queued_connections.fetch_add(1, Ordering::SeqCst);
+            server.yield_idle_connection();
+            let server = Arc::clone(&server);
+            pool.execute(move || {
+                let _active = ActiveConnection::new(&server.active_connections);
+                server.queued_connections.fetch_sub(1, Ordering::SeqCst);
+                server.handle_connection(stream);
+            });
+        }
+        drop(self.listener);
+        if !pool.join_timeout(server.shutdown_timeout) {
+            eprintln!("Shutdown timed out with requests in progress");
+        }
+        Ok(())
+    }
+}
+
+/// Default handler for requests whose path does not match any route.
+fn not_found(_request: &Request) -> Response {
+    Response::new(Status::NotFound)
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Server {
@@ -21,6 +155,17 @@ impl Server {
             address,
             port,
             router,
+            not_found: Box::new(not_found),
+            workers: 4,
+            keep_alive_timeout: Duration::from_secs(5),
+            max_requests: 100,
+            max_header_size: 8 * 1024,
+            max_body_size: 1024 * 1024,
+            shared: Arc::new(Shared::default()),
+            shutdown_timeout: Duration::from_secs(10),
+            queued_connections: AtomicUsize::new(0),
+            active_connections: AtomicUsize::new(0),
+            next_idle_id: AtomicUsize::new(0),
         }
     }
 
@@ -32,6 +177,7 @@ impl Server {
         }
     }
 
+    /// Register a handler which serves every method on the path.
     pub fn route<F>(mut self, path: &str, handler: F) -> Self
     where
         F: Handler,
@@ -40,41 +186,733 @@ impl Server {
         self
     }
 
-    fn parse_request(stream: &mut net::TcpStream) -> Result<Request, Box<dyn Error>> {
-        let mut buffer = [0; 512];
-        stream.read(&mut buffer).expect("Error reading stream");
-        // Todo: remove unwrap()
-        let buffer = String::from_utf8(buffer.to_vec()).unwrap();
-        Request::new(&buffer)
-    }
-
-    pub fn run(&mut self) -> io::Result<()> {
-        let bound_address = net::IpAddr::from_str(&self.address).unwrap();
-        let bound_address = net::SocketAddr::new(bound_address, self.port);
-        let listener = net::TcpListener::bind(bound_address)?;
-        println!("Server listening on {}", listener.local_addr().unwrap());
-        for stream in listener.incoming() {
-            match stream {
-                Ok(mut stream) => {
-                    let request = match Self::parse_request(&mut stream) {
-                        Ok(request) => request,
-                        Err(err) => {
-                            eprintln!("{}", err);
-                            continue;
-                        }
-                    };
-                    let handler = match self.router.find(&request.uri) {
-                        Some(handler) => handler,
-                        None => panic!("Handler not set for the route: {}", request.uri),
-                    };
-                    let response: Vec<u8> = handler.handle(&request).into();
-                    dbg!(request);
-                    stream.write(&response)?;
-                    stream.flush()?;
+    /// Register a handler which serves only the method on the path.
+    pub fn route_method<F>(mut self, method: Method, path: &str, handler: F) -> Self
+    where
+        F: Handler,
+    {
+        self.router.add_method_route(method, path, handler);
+        self
+    }
+
+    pub fn get<F: Handler>(self, path: &str, handler: F) -> Self {
+        self.route_method(Method::Get, path, handler)
+    }
+
+    pub fn post<F: Handler>(self, path: &str, handler: F) -> Self {
+        self.route_method(Method::Post, path, handler)
+    }
+
+    pub fn put<F: Handler>(self, path: &str, handler: F) -> Self {
+        self.route_method(Method::Put, path, handler)
+    }
+
+    pub fn delete<F: Handler>(self, path: &str, handler: F) -> Self {
+        self.route_method(Method::Delete, path, handler)
+    }
+
+    pub fn patch<F: Handler>(self, path: &str, handler: F) -> Self {
+        self.route_method(Method::Patch, path, handler)
+    }
+
+    /// Replace the routes with the router built beforehand.
+    pub fn router(self, router: Router) -> Self {
+        Self { router, ..self }
+    }
+
+    /// Set a handler for requests whose path does not match any route.
+    /// By default, 404 Not Found is returned.
+    pub fn not_found<F: Handler>(self, handler: F) -> Self {
+        Self {
+            not_found: Box::new(handler),
+            ..self
+        }
+    }
+
+    /// Set the number of worker threads which handle connections concurrently.
+    /// At least one worker is used even if 0 is given.
+    pub fn workers(self, workers: usize) -> Self {
+        Self {
+            workers: workers.max(1),
+            ..self
+        }
+    }
+
+    /// Set how long an idle persistent connection is kept open.
+    pub fn keep_alive_timeout(self, keep_alive_timeout: Duration) -> Self {
+        Self {
+            keep_alive_timeout,
+            ..self
+        }
+    }
+
+    /// Set the maximum number of requests served on one persistent connection.
+    /// At least one request is served even if 0 is given.
+    pub fn max_requests(self, max_requests: usize) -> Self {
+        Self {
+            max_requests: max_requests.max(1),
+            ..self
+        }
+    }
+
+    /// Set the maximum size of the request line and headers in bytes.
+    /// Larger requests are rejected with 431 Request Header Fields Too Large.
+    pub fn max_header_size(self, max_header_size: usize) -> Self {
+        Self {
+            max_header_size,
+            ..self
+        }
+    }
+
+    /// Set the maximum size of request bodies in bytes.
+    /// Larger requests are rejected with 413 Payload Too Large.
+    pub fn max_body_size(self, max_body_size: usize) -> Self {
+        Self {
+            max_body_size,
+            ..self
+        }
+    }
+
+    /// Set how long the server waits for requests in progress after shutdown is requested.
+    pub fn shutdown_timeout(self, shutdown_timeout: Duration) -> Self {
+        Self {
+            shutdown_timeout,
+            ..self
+        }
+    }
+
+    /// Return a handle to stop the server after `run` is called.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    fn is_shutting_down(&self) -> bool {
+        self.shared.shutdown.load(Ordering::SeqCst)
+    }
+
+    /// Find a handler for the request and return its response.
+    /// Values of path parameters are stored into the request.
+    /// If no route matches the path, the response of the not found handler is returned.
+    /// If handlers are registered for the path but not for the method, return
+    /// 405 Method Not Allowed with `Allow` header. HEAD is served by the GET handler
+    /// unless a handler is registered for it.
+    pub(crate) fn respond(&self, request: &mut Request) -> Response {
+        let (endpoint, params) = match self.router.find(&request.path) {
+            Some(found) => found,
+            None => return self.not_found.handle(request),
+        };
+        request.params = params;
+        match endpoint.handler(&request.method) {
+            Some(handler) => handler.handle(request),
+            None => {
+                let allowed_methods = endpoint
+                    .allowed_methods()
+                    .iter()
+                    .map(|method| method.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", ");
+                let mut response = Response::new(Status::MethodNotAllowed);
+                response.headers.insert(HeaderName::ALLOW, allowed_methods);
+                response
+            }
+        }
+    }
+
+    /// Bind the address and return `RunningServer`, which accepts connections when its
+    /// `run` is called. Binding port 0 lets the OS choose a free port, which is given by
+    /// `RunningServer::local_addr`.
+    pub fn listen(self) -> io::Result<RunningServer> {
+        // Brackets are allowed around an IPv6 address such as "[::1]".
+        let address = self.address.trim_start_matches('[').trim_end_matches(']');
+        let listener = net::TcpListener::bind((address, self.port))?;
+        let mut wake_addr = listener.local_addr()?;
+        if wake_addr.ip().is_unspecified() {
+            match wake_addr {
+                net::SocketAddr::V4(_) => wake_addr.set_ip(net::Ipv4Addr::LOCALHOST.into()),
+                net::SocketAddr::V6(_) => wake_addr.set_ip(net::Ipv6Addr::LOCALHOST.into()),
+            }
+        }
+        *self.shared.wake_addr.lock().unwrap() = Some(wake_addr);
+        Ok(RunningServer {
+            server: self,
+            listener,
+        })
+    }
+
+    /// Bind the address and accept connections until shutdown is requested through
+    /// `ShutdownHandle`.
+    pub fn run(self) -> io::Result<()> {
+        self.listen()?.run()
+    }
+
+    /// Serve requests on the connection until the client or the server closes it.
+    /// Responses are written in the order of requests.
+    fn handle_connection(&self, stream: net::TcpStream) {
+        let mut connection = Connection::new(stream)
+            .max_head_size(self.max_header_size)
+            .max_body_size(self.max_body_size);
+        for count in 1..=self.max_requests {
+            if !connection.has_buffered() && !self.wait_for_request(connection.get_ref(), count > 1)
+            {
+                return;
+            }
+            let mut request = match connection.read_request() {
+                Ok(Some(request)) => request,
+                Ok(None) => return,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    if let Some(mut response) = Self::error_response(err.as_ref()) {
+                        Self::set_connection(&mut response, None);
+                        let _ = connection.write_response(response);
+                    }
+                    return;
                 }
-                Err(err) => return Err(io::Error::new(io::ErrorKind::Other, err)),
+            };
+            let keep_alive = request.keep_alive() && count < self.max_requests;
+            let mut response = self.respond(&mut request);
+            // Requests received during shutdown are served, but the connection is closed.
+            let keep_alive = keep_alive && !self.is_shutting_down();
+            Self::finalize(&request, &mut response, keep_alive);
+            if let Err(err) = connection.write_response(response) {
+                eprintln!("{}", err);
+                return;
+            }
+            if !keep_alive {
+                return;
             }
         }
-        Ok(())
+    }
+
+    /// Wait until bytes of the next request arrive on the connection.
+    /// Return `false` if the connection should be closed because the client closed it, the
+    /// keep-alive timeout expired or the server is shutting down. A connection which has
+    /// served requests is also closed as soon as another connection waits for a worker, so
+    /// that idle persistent connections cannot occupy all the workers.
+    fn wait_for_request(&self, stream: &net::TcpStream, has_served: bool) -> bool {
+        let idle = match stream.try_clone() {
+            Ok(idle) => IdleConnection {
+                stream: idle,
+                has_served,
+            },
+            Err(_) => return false,
+        };
+        let id = self.next_idle_id.fetch_add(1, Ordering::SeqCst);
+        {
+            // Checked under the lock so that `ShutdownHandle::shutdown` and
+            // `yield_idle_connection` either see this connection or are seen here.
+            let mut idle_connections = self.shared.idle_connections.lock().unwrap();
+            if self.is_shutting_down() || (has_served && self.is_pool_busy()) {
+                return false;
+            }
+            idle_connections.insert(id, idle);
+        }
+        let has_request = stream
+            .set_read_timeout(Some(self.keep_alive_timeout))
+            .is_ok()
+            && matches!(stream.peek(&mut [0]), Ok(length) if length > 0);
+        self.shared.idle_connections.lock().unwrap().remove(&id);
+        has_request
+    }
+
+    /// Check if an accepted connection waits for a worker because all of them are busy.
+    fn is_pool_busy(&self) -> bool {
+        let queued = self.queued_connections.load(Ordering::SeqCst);
+        let active = self.active_connections.load(Ordering::SeqCst);
+        queued + active > self.workers
+    }
+
+    /// Close an idle connection which has served requests if a newly accepted connection
+    /// has no worker to handle it.
+    fn yield_idle_connection(&self) {
+        let mut idle_connections = self.shared.idle_connections.lock().unwrap();
+        if !self.is_pool_busy() {
+            return;
+        }
+        let id = idle_connections
+            .iter()
+            .find(|(_, idle)| idle.has_served)
+            .map(|(id, _)| *id);
+        if let Some(idle) = id.and_then(|id| idle_connections.remove(&id)) {
+            let _ = idle.stream.shutdown(net::Shutdown::Both);
+        }
+    }
+
+    /// Return a response for a request which cannot be read, or `None` if the connection is
+    /// broken and no response can be sent.
+    pub(crate) fn error_response(err: &(dyn Error + 'static)) -> Option<Response> {
+        let status = match err.downcast_ref::<RequestParseError>() {
+            Some(RequestParseError::HeadTooLarge) => Status::RequestHeaderFieldsTooLarge,
+            Some(RequestParseError::BodyTooLarge) => Status::PayloadTooLarge,
+            Some(RequestParseError::UnsupportedTransferEncoding) => Status::NotImplemented,
+            Some(_) => Status::BadRequest,
+            None if err.is::<std::str::Utf8Error>() => Status::BadRequest,
+            None => return None,
+        };
+        Some(Response::new(status))
+    }
+
+    /// Complete the response to the request before it is written: set headers to manage
+    /// the connection and `Content-Length`, and drop the body of a response to HEAD.
+    /// `TestClient` also calls this to respond to requests in the same way.
+    pub(crate) fn finalize(request: &Request, response: &mut Response, keep_alive: bool) {
+        Self::set_connection(response, Some(request.version).filter(|_| keep_alive));
+        // Bytes of a body sent to HEAD would be read as the next response.
+        if request.method == Method::Head {
+            response.omit_body();
+        } else {
+            response.set_content_length();
+        }
+    }
+
+    /// Set headers to manage the connection. If `keep_alive_version` is `None`, the
+    /// connection is going to be closed.
+    pub(crate) fn set_connection(response: &mut Response, keep_alive_version: Option<Version>) {
+        match keep_alive_version {
+            None => {
+                response
+                    .headers
+                    .insert(HeaderName::CONNECTION, "close".to_string());
+            }
+            Some(Version::Http10) => {
+                response
+                    .headers
+                    .insert(HeaderName::CONNECTION, "keep-alive".to_string());
+            }
+            Some(Version::Http11) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::headers::HeaderName;
+    use crate::request::Request;
+    use crate::response::Response;
+    use crate::server::Server;
+    use crate::status::Status;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    // Start the server on a port chosen by the OS in a new thread and return the port.
+    fn spawn(server: Server) -> u16 {
+        let server = server.bind("127.0.0.1", 0).listen().unwrap();
+        let port = server.local_addr().unwrap().port();
+        thread::spawn(move || server.run());
+        port
+    }
+
+    fn connect(port: u16) -> TcpStream {
+        TcpStream::connect(("127.0.0.1", port)).unwrap()
+    }
+
+    // Read the status line and headers of one response.
+    fn read_head(stream: &mut TcpStream) -> String {
+        let mut head = Vec::new();
+        let mut byte = [0];
+        while !head.ends_with(b"\r\n\r\n") {
+            stream.read_exact(&mut byte).unwrap();
+            head.push(byte[0]);
+        }
+        String::from_utf8(head).unwrap()
+    }
+
+    // Read one response whose body length is given by `Content-Length` header.
+    fn read_response(stream: &mut TcpStream) -> String {
+        let head = read_head(stream);
+        let content_length = head
+            .split("\r\n")
+            .find_map(|line| line.strip_prefix("Content-Length: "))
+            .map_or(0, |length| length.parse().unwrap());
+        let mut body = vec![0; content_length];
+        stream.read_exact(&mut body).unwrap();
+        head + &String::from_utf8(body).unwrap()
+    }
+
+    // Send a raw request to the server listening on the port and return the raw response.
+    fn send(port: u16, request: &str) -> String {
+        let mut stream = connect(port);
+        stream.write_all(request.as_bytes()).unwrap();
+        read_response(&mut stream)
+    }
+
+    // Check if the server has closed the connection.
+    fn is_closed(stream: &mut TcpStream) -> bool {
+        let mut rest = Vec::new();
+        stream.read_to_end(&mut rest).unwrap();
+        rest.is_empty()
+    }
+
+    fn ok_handler(_request: &Request) -> Response {
+        Response::new(Status::OK)
+    }
+
+    fn param_handler(request: &Request) -> Response {
+        let mut response = Response::new(Status::OK);
+        response.set_body(request.param("id").unwrap().to_string());
+        response
+    }
+
+    #[test]
+    fn test_respond_with_param() {
+        let server = Server::new().get("/users/:id", param_handler);
+        let mut request = Request::new(b"GET /users/42 HTTP/1.1\r\n\r\n").unwrap();
+        let response = server.respond(&mut request);
+        assert_eq!(response.body.as_bytes(), Some(&b"42"[..]));
+    }
+
+    #[test]
+    fn test_respond_with_method() {
+        let server = Server::new()
+            .get("/users", ok_handler)
+            .post("/users", ok_handler);
+        let mut request = Request::new(b"POST /users HTTP/1.1\r\n\r\n").unwrap();
+        let response = server.respond(&mut request);
+        assert_eq!(response.status, Status::OK);
+    }
+
+    #[test]
+    fn test_respond_method_not_allowed() {
+        let server = Server::new()
+            .get("/users", ok_handler)
+            .post("/users", ok_handler);
+        let mut request = Request::new(b"DELETE /users HTTP/1.1\r\n\r\n").unwrap();
+        let response = server.respond(&mut request);
+        assert_eq!(response.status, Status::MethodNotAllowed);
+        assert_eq!(
+            response.headers.get(&HeaderName::ALLOW),
+            Some("GET, HEAD, POST")
+        );
+    }
+
+    #[test]
+    fn test_respond_not_found() {
+        let server = Server::new().get("/", ok_handler);
+        let mut request = Request::new(b"GET /unknown HTTP/1.1\r\n\r\n").unwrap();
+        let response = server.respond(&mut request);
+        assert_eq!(response.status, Status::NotFound);
+    }
+
+    #[test]
+    fn test_respond_custom_not_found() {
+        let server = Server::new().not_found(|_request: &Request| {
+            let mut response = Response::new(Status::NotFound);
+            response.set_body("No such page".to_string());
+            response
+        });
+        let mut request = Request::new(b"GET /unknown HTTP/1.1\r\n\r\n").unwrap();
+        let response = server.respond(&mut request);
+        assert_eq!(response.status, Status::NotFound);
+        assert_eq!(response.body.as_bytes(), Some(&b"No such page"[..]));
+    }
+
+    #[test]
+    fn test_run_after_not_found() {
+        let port = spawn(Server::new().get("/", ok_handler));
+        let response = send(port, "GET /unknown HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        let response = send(port, "GET / HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    #[test]
+    fn test_run_concurrently() {
+        let port = spawn(
+            Server::new()
+                .workers(2)
+                .get("/sleep", |_request: &Request| {
+                    thread::sleep(Duration::from_millis(500));
+                    Response::new(Status::OK)
+                }),
+        );
+        let start = Instant::now();
+        let clients = (0..2)
+            .map(|_| thread::spawn(move || send(port, "GET /sleep HTTP/1.1\r\n\r\n")))
+            .collect::<Vec<_>>();
+        for client in clients {
+            assert!(client.join().unwrap().starts_with("HTTP/1.1 200 OK\r\n"));
+        }
+        // Handling two requests one by one takes at least 1 second.
+        assert!(start.elapsed() < Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_run_with_zero_workers() {
+        let port = spawn(Server::new().workers(0).get("/", ok_handler));
+        let response = send(port, "GET / HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    fn body_handler(request: &Request) -> Response {
+        let mut response = Response::new(Status::OK);
+        response.set_body(request.uri.clone());
+        response
+    }
+
+    #[test]
+    fn test_run_keep_alive() {
+        let port = spawn(Server::new().route("/*", body_handler));
+        let mut stream = connect(port);
+        stream.write_all(b"GET /a HTTP/1.1\r\n\r\n").unwrap();
+        assert!(read_response(&mut stream).ends_with("\r\n\r\n/a"));
+        stream.write_all(b"GET /b HTTP/1.1\r\n\r\n").unwrap();
+        assert!(read_response(&mut stream).ends_with("\r\n\r\n/b"));
+
+        stream
+            .write_all(b"GET /c HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let response = read_response(&mut stream);
+        assert!(response.contains("\r\nConnection: close\r\n"));
+        assert!(is_closed(&mut stream));
+    }
+
+    #[test]
+    fn test_run_pipelining() {
+        let port = spawn(Server::new().route("/*", body_handler));
+        let mut stream = connect(port);
+        stream
+            .write_all(b"GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n\r\nGET /c HTTP/1.1\r\n\r\n")
+            .unwrap();
+        assert!(read_response(&mut stream).ends_with("/a"));
+        assert!(read_response(&mut stream).ends_with("/b"));
+        assert!(read_response(&mut stream).ends_with("/c"));
+    }
+
+    #[test]
+    fn test_run_head_pipelining() {
+        let port = spawn(Server::new().route("/*", body_handler));
+        let mut stream = connect(port);
+        stream
+            .write_all(b"HEAD /abc HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n\r\n")
+            .unwrap();
+        // The response to HEAD has `Content-Length` of the body for GET, but no body.
+        let head = read_head(&mut stream);
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains("\r\nContent-Length: 4\r\n"));
+        let response = read_response(&mut stream);
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("\r\n\r\n/b"));
+    }
+
+    #[test]
+    fn test_run_chunked_pipelining() {
+        let port = spawn(Server::new().route("/*", body_handler));
+        let mut stream = connect(port);
+        stream
+            .write_all(
+                b"POST /a HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+                1a\r\nGET /smuggled HTTP/1.1\r\n\r\n\r\n0\r\n\r\nGET /b HTTP/1.1\r\n\r\n",
+            )
+            .unwrap();
+        // The connection is closed since the end of the chunked body is unknown.
+        let response = read_response(&mut stream);
+        assert!(response.starts_with("HTTP/1.1 501 Not Implemented\r\n"));
+        assert!(response.contains("\r\nConnection: close\r\n"));
+        assert!(is_closed(&mut stream));
+    }
+
+    #[test]
+    fn test_run_http10() {
+        let port = spawn(Server::new().route("/*", body_handler));
+        let mut stream = connect(port);
+        stream.write_all(b"GET /a HTTP/1.0\r\n\r\n").unwrap();
+        read_response(&mut stream);
+        assert!(is_closed(&mut stream));
+
+        let mut stream = connect(port);
+        stream
+            .write_all(b"GET /a HTTP/1.0\r\nConnection: keep-alive\r\n\r\n")
+            .unwrap();
+        let response = read_response(&mut stream);
+        assert!(response.contains("\r\nConnection: keep-alive\r\n"));
+        stream.write_all(b"GET /b HTTP/1.0\r\n\r\n").unwrap();
+        assert!(read_response(&mut stream).ends_with("/b"));
+        assert!(is_closed(&mut stream));
+    }
+
+    #[test]
+    fn test_run_max_requests_and_timeout() {
+        let port = spawn(
+            Server::new()
+                .max_requests(2)
+                .keep_alive_timeout(Duration::from_millis(200))
+                .route("/*", body_handler),
+        );
+        let mut stream = connect(port);
+        stream
+            .write_all(b"GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n\r\n")
+            .unwrap();
+        read_response(&mut stream);
+        assert!(read_response(&mut stream).contains("\r\nConnection: close\r\n"));
+        assert!(is_closed(&mut stream));
+
+        // The idle connection is closed after the timeout.
+        let mut stream = connect(port);
+        assert!(is_closed(&mut stream));
+    }
+
+    #[test]
+    fn test_run_zero_max_requests() {
+        let port = spawn(Server::new().max_requests(0).route("/*", body_handler));
+        let mut stream = connect(port);
+        stream
+            .write_all(b"GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n\r\n")
+            .unwrap();
+        let response = read_response(&mut stream);
+        assert!(response.contains("\r\nConnection: close\r\n"));
+        assert!(response.ends_with("/a"));
+        assert!(is_closed(&mut stream));
+    }
+
+    #[test]
+    fn test_run_idle_connection_yields_worker() {
+        let port = spawn(Server::new().workers(1).route("/*", body_handler));
+        let mut idle = connect(port);
+        idle.write_all(b"GET /a HTTP/1.1\r\n\r\n").unwrap();
+        read_response(&mut idle);
+
+        // The only worker waits on the idle connection until another client connects.
+        let start = Instant::now();
+        assert!(send(port, "GET /b HTTP/1.1\r\n\r\n").ends_with("/b"));
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(is_closed(&mut idle));
+    }
+
+    #[test]
+    fn test_run_too_large_request() {
+        let port = spawn(
+            Server::new()
+                .max_header_size(64)
+                .max_body_size(4)
+                .route("/*", body_handler),
+        );
+        let response = send(
+            port,
+            "GET / HTTP/1.1\r\nUser-Agent: Mozilla/5.0 (X11; Linux x86_64; rv:78.0)\r\n\r\n",
+        );
+        assert!(response.starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"));
+        let response = send(port, "POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello");
+        assert!(response.starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+        let response = send(port, "POST / HTTP/1.1\r\nContent-Length: 4\r\n\r\nhell");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        let mut stream = connect(port);
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: \xff\r\n\r\n")
+            .unwrap();
+        let response = read_response(&mut stream);
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+
+    #[test]
+    fn test_run_shutdown() {
+        for _ in 0..10 {
+            let server = Server::new()
+                .bind("127.0.0.1", 0)
+                .get("/", ok_handler)
+                .listen()
+                .unwrap();
+            let port = server.local_addr().unwrap().port();
+            let handle = server.shutdown_handle();
+            let running = thread::spawn(move || server.run());
+            assert!(send(port, "GET / HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 200 OK\r\n"));
+            handle.shutdown();
+            running.join().unwrap().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_run_shutdown_with_requests_in_progress() {
+        let server = Server::new()
+            .bind("127.0.0.1", 0)
+            .get("/sleep", |_request: &Request| {
+                thread::sleep(Duration::from_millis(300));
+                Response::new(Status::OK)
+            })
+            .listen()
+            .unwrap();
+        let port = server.local_addr().unwrap().port();
+        let handle = server.shutdown_handle();
+        let running = thread::spawn(move || server.run());
+
+        let mut stream = connect(port);
+        stream.write_all(b"GET /sleep HTTP/1.1\r\n\r\n").unwrap();
+        // An idle connection must not delay the shutdown.
+        let _idle = connect(port);
+        thread::sleep(Duration::from_millis(100));
+        let start = Instant::now();
+        handle.shutdown();
+
+        // The request in progress is finished and then the connection is closed.
+        let response = read_response(&mut stream);
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("\r\nConnection: close\r\n"));
+        running.join().unwrap().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(TcpStream::connect(("127.0.0.1", port)).is_err());
+    }
+
+    #[test]
+    fn test_run_shutdown_with_idle_connection() {
+        // Bound to the unspecified address, shutdown connects to the loopback address.
+        let server = Server::new()
+            .bind("0.0.0.0", 0)
+            .get("/", ok_handler)
+            .listen()
+            .unwrap();
+        let port = server.local_addr().unwrap().port();
+        let handle = server.shutdown_handle();
+        let running = thread::spawn(move || server.run());
+
+        // The connection waits for its next request until the keep-alive timeout.
+        let mut idle = connect(port);
+        idle.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        read_response(&mut idle);
+        let start = Instant::now();
+        handle.shutdown();
+        running.join().unwrap().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(is_closed(&mut idle));
+    }
+
+    #[test]
+    fn test_run_shutdown_timeout() {
+        let server = Server::new()
+            .bind("127.0.0.1", 0)
+            .shutdown_timeout(Duration::from_millis(100))
+            .get("/sleep", |_request: &Request| {
+                thread::sleep(Duration::from_secs(3));
+                Response::new(Status::OK)
+            })
+            .listen()
+            .unwrap();
+        let port = server.local_addr().unwrap().port();
+        let handle = server.shutdown_handle();
+        let running = thread::spawn(move || server.run());
+
+        let mut stream = connect(port);
+        stream.write_all(b"GET /sleep HTTP/1.1\r\n\r\n").unwrap();
+        thread::sleep(Duration::from_millis(100));
+        let start = Instant::now();
+        handle.shutdown();
+        running.join().unwrap().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_listen_ipv6_and_hostname() {
+        for address in &["::1", "[::1]", "localhost"] {
+            let server = match Server::new().bind(address, 0).listen() {
+                Ok(server) => server,
+                // IPv6 may be disabled on the machine.
+                Err(_) if address.contains(':') => continue,
+                Err(err) => panic!("{}", err),
+            };
+            let local_addr = server.local_addr().unwrap();
+            assert_ne!(local_addr.port(), 0);
+            assert!(local_addr.ip().is_loopback());
+        }
     }
 }
@@ -1,32 +1,1082 @@
 use crate::handler::Handler;
-use crate::request::Request;
+use crate::headers::HeaderName;
+use crate::http_date;
+use crate::mime;
+use crate::range::{self, ByteRange, RangeError};
+use crate::request::{Method, Request};
 use crate::responder::Responder;
-use crate::response::Response;
-use std::fs::File;
-use std::path::{Path, PathBuf};
+use crate::response::{Body, Response};
+use crate::status::Status;
+use crate::uri::{format_query, percent_encode};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs::{self, File};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How to treat symbolic links whose targets are outside of the root directory.
+/// Symbolic links to files under the root are always followed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SymlinkPolicy {
+    /// Respond 403 Forbidden.
+    Forbid,
+    /// Serve the target of the link.
+    Follow,
+}
 
 #[derive(Clone)]
 pub struct StaticFiles {
     root: PathBuf,
+    symlink_policy: SymlinkPolicy,
+    autoindex: bool,
+    /// Content type and body of error responses for each status.
+    error_bodies: HashMap<Status, (String, Vec<u8>)>,
 }
 
 impl StaticFiles {
     pub fn new<P: AsRef<Path>>(root: P) -> Self {
         let root: PathBuf = root.as_ref().into();
-        Self { root }
+        Self {
+            root,
+            symlink_policy: SymlinkPolicy::Forbid,
+            autoindex: false,
+            error_bodies: HashMap::new(),
+        }
+    }
+
+    /// Set how to treat symbolic links leading outside of the root directory.
+    pub fn symlinks(self, symlink_policy: SymlinkPolicy) -> Self {
+        Self {
+            symlink_policy,
+            ..self
+        }
+    }
+
+    /// Respond a listing of files for directories without `index.html` if `true`.
+    /// Such directories are responded 404 Not Found by default.
+    pub fn autoindex(self, autoindex: bool) -> Self {
+        Self { autoindex, ..self }
+    }
+
+    /// Set the body of responses with an error status such as 404 Not Found.
+    /// Error responses have no body by default.
+    pub fn error_body<B: Into<Vec<u8>>>(
+        mut self,
+        status: Status,
+        content_type: &str,
+        body: B,
+    ) -> Self {
+        self.error_bodies
+            .insert(status, (content_type.to_string(), body.into()));
+        self
+    }
+
+    fn error_response(&self, status: Status) -> Response {
+        let body = self.error_bodies.get(&status).cloned();
+        let mut response = Response::new(status);
+        if let Some((content_type, body)) = body {
+            response
+                .headers
+                .insert(HeaderName::CONTENT_TYPE, content_type);
+            response.set_body(body);
+        }
+        response
+    }
+
+    /// Resolve the percent-decoded path of a request into the path of a file under the root
+    /// directory. `..` is removed with the preceding segment as in URLs, such as
+    /// `sub/../index.html` into `index.html`. Return the status to respond with if the path
+    /// is invalid or escapes from the root.
+    fn resolve(&self, request_path: &str) -> Result<PathBuf, Status> {
+        if request_path.contains('\0') {
+            return Err(Status::BadRequest);
+        }
+        // `..` above the root and absolute paths are rejected before touching the file system.
+        let mut relative_path = PathBuf::new();
+        for component in Path::new(request_path.trim_start_matches('/')).components() {
+            match component {
+                Component::Normal(segment) => relative_path.push(segment),
+                Component::CurDir => {}
+                Component::ParentDir if relative_path.pop() => {}
+                _ => return Err(Status::Forbidden),
+            }
+        }
+
+        self.confine(&self.canonical_root()?.join(relative_path))
+    }
+
+    fn canonical_root(&self) -> Result<PathBuf, Status> {
+        let current_dir = std::env::current_dir().map_err(|_| Status::InternalServerError)?;
+        current_dir
+            .join(&self.root)
+            .canonicalize()
+            .map_err(|err| io_error_status(&err))
+    }
+
+    /// Canonicalize the path and check that it is under the root directory.
+    fn confine(&self, path: &Path) -> Result<PathBuf, Status> {
+        self.confine_to(&self.canonical_root()?, path)
+    }
+
+    /// Canonicalize the path and check that it is under `root`, which is the canonical path
+    /// of the root directory.
+    fn confine_to(&self, root: &Path, path: &Path) -> Result<PathBuf, Status> {
+        let path = path.canonicalize().map_err(|err| io_error_status(&err))?;
+        // The canonical path is outside of the root only if a symbolic link leads there.
+        if !path.starts_with(root) && self.symlink_policy == SymlinkPolicy::Forbid {
+            return Err(Status::Forbidden);
+        }
+        Ok(path)
+    }
+
+    fn file_response(&self, request: &Request, path: &Path) -> Response {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(err) => return self.error_response(io_error_status(&err)),
+        };
+        let last_modified = metadata.modified().ok();
+        let etag = last_modified.map(|modified| etag(metadata.len(), modified));
+
+        let mut response = match evaluate_preconditions(request, etag.as_deref(), last_modified) {
+            Some(status) => Response::new(status),
+            None => match self.content_response(request, path, &metadata, etag.as_deref()) {
+                Ok(response) => response,
+                Err(err) => return self.error_response(io_error_status(&err)),
+            },
+        };
+        if response.status != Status::PreconditionFailed {
+            if let Some(etag) = etag {
+                response.headers.insert(HeaderName::ETAG, etag);
+            }
+            if let Some(last_modified) = last_modified {
+                response
+                    .headers
+                    .insert(HeaderName::LAST_MODIFIED, http_date::format(last_modified));
+            }
+        }
+        response
+    }
+
+    /// Respond the whole file or the ranges of it requested by `Range` header.
+    fn content_response(
+        &self,
+        request: &Request,
+        path: &Path,
+        metadata: &fs::Metadata,
+        etag: Option<&str>,
+    ) -> io::Result<Response> {
+        let ranges = requested_ranges(request, etag, metadata.modified().ok())
+            .map(|value| range::parse(value, metadata.len()));
+        let mut response = match ranges {
+            Some(Ok(ranges)) => partial_response(path, &ranges, metadata.len())?,
+            Some(Err(RangeError::Unsatisfiable)) => {
+                let mut response = self.error_response(Status::RangeNotSatisfiable);
+                response.headers.insert(
+                    HeaderName::CONTENT_RANGE,
+                    format!("bytes */{}", metadata.len()),
+                );
+                response
+            }
+            // A malformed `Range` header is ignored.
+            None | Some(Err(RangeError::Invalid)) => path.to_response()?,
+        };
+        response
+            .headers
+            .insert(HeaderName::ACCEPT_RANGES, "bytes".to_string());
+        Ok(response)
+    }
+
+    /// Respond `index.html` in the directory or a listing of the directory.
+    /// Requests without a trailing slash are redirected to the path with it.
+    fn directory_response(&self, request: &Request, dir: &Path) -> Response {
+        // Relative links in the index would be resolved against the parent directory
+        // without a trailing slash.
+        if !request.path.ends_with('/') {
+            let mut location = directory_location(&request.path);
+            if !request.query.is_empty() {
+                location.push('?');
+                location.push_str(&format_query(&request.query));
+            }
+            let mut response = Response::new(Status::MovedPermanently);
+            response.headers.insert(HeaderName::LOCATION, location);
+            return response;
+        }
+
+        let index = dir.join("index.html");
+        if index.is_file() {
+            return match self.confine(&index) {
+                Ok(index) => self.file_response(request, &index),
+                Err(status) => self.error_response(status),
+            };
+        }
+        if !self.autoindex {
+            return self.error_response(Status::NotFound);
+        }
+        let title = &request.path;
+        let root = match self.canonical_root() {
+            Ok(root) => root,
+            Err(status) => return self.error_response(status),
+        };
+        match self.listing(title, dir, &root) {
+            Ok(listing) => {
+                let mut response = Response::new(Status::OK);
+                response.headers.insert(
+                    HeaderName::CONTENT_TYPE,
+                    mime::from_extension("html").unwrap().to_string(),
+                );
+                response.set_body(listing);
+                response
+            }
+            Err(err) => self.error_response(io_error_status(&err)),
+        }
+    }
+
+    /// Render an HTML listing of the files in the directory.
+    /// Entries which cannot be read, such as broken symbolic links, are omitted.
+    fn listing(&self, title: &str, dir: &Path, root: &Path) -> io::Result<String> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(_) => continue,
+            };
+            // Files with non UTF-8 names cannot be requested.
+            let name = match entry.file_name().into_string() {
+                Ok(name) => name,
+                Err(_) => continue,
+            };
+            // Skip broken symbolic links and the ones leading outside of the root.
+            let path = match self.confine_to(root, &entry.path()) {
+                Ok(path) => path,
+                Err(_) => continue,
+            };
+            match fs::metadata(path) {
+                Ok(metadata) => entries.push((name, metadata)),
+                Err(_) => continue,
+            }
+        }
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let title = escape_html(title);
+        let mut html = format!(
+            concat!(
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n",
+                "<title>Index of {0}</title>\n</head>\n<body>\n<h1>Index of {0}</h1>\n<table>\n",
+                "<tr><th>Name</th><th>Size</th><th>Last Modified</th></tr>\n"
+            ),
+            title
+        );
+        if dir != root {
+            html.push_str("<tr><td><a href=\"../\">../</a></td><td>-</td><td>-</td></tr>\n");
+        }
+        for (name, metadata) in entries {
+            let (suffix, size) = if metadata.is_dir() {
+                ("/", "-".to_string())
+            } else {
+                ("", metadata.len().to_string())
+            };
+            let modified = metadata
+                .modified()
+                .map(http_date::format)
+                .unwrap_or_else(|_| "-".to_string());
+            writeln!(
+                html,
+                "<tr><td><a href=\"{}{}\">{}{}</a></td><td>{}</td><td>{}</td></tr>",
+                percent_encode(&name),
+                suffix,
+                escape_html(&name),
+                suffix,
+                size,
+                modified
+            )
+            .unwrap();
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+        Ok(html)
+    }
+}
+
+/// Normalize the decoded path of a directory into the path to redirect to, which has a
+/// single leading slash and a trailing slash. Segments are percent-encoded, so that a path
+/// such as `//example.com` cannot be taken as a URL of another host.
+fn directory_location(path: &str) -> String {
+    let mut segments = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    let mut location = "/".to_string();
+    for segment in segments {
+        location.push_str(&percent_encode(segment));
+        location.push('/');
+    }
+    location
+}
+
+/// Create an entity tag of a file from its size and modification time.
+fn etag(len: u64, modified: SystemTime) -> String {
+    let modified = modified
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0));
+    format!(
+        "\"{:x}-{:x}-{:x}\"",
+        len,
+        modified.as_secs(),
+        modified.subsec_nanos()
+    )
+}
+
+/// Evaluate `If-None-Match` and `If-Modified-Since` of the request as described in RFC 7232.
+/// Return the status to respond with instead of the file if the condition is false.
+fn evaluate_preconditions(
+    request: &Request,
+    etag: Option<&str>,
+    last_modified: Option<SystemTime>,
+) -> Option<Status> {
+    let is_get_or_head = matches!(request.method, Method::Get | Method::Head);
+    // `If-Modified-Since` is ignored if `If-None-Match` is present.
+    if let Some(if_none_match) = request.headers.get(&HeaderName::IF_NONE_MATCH) {
+        // Weak comparison ignores the `W/` prefix of entity tags.
+        let opaque_tag = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+        let matches = if_none_match.trim() == "*"
+            || etag.is_some_and(|etag| {
+                if_none_match
+                    .split(',')
+                    .any(|tag| opaque_tag(tag) == opaque_tag(etag))
+            });
+        return match (matches, is_get_or_head) {
+            (false, _) => None,
+            (true, true) => Some(Status::NotModified),
+            (true, false) => Some(Status::PreconditionFailed),
+        };
+    }
+
+    if !is_get_or_head {
+        return None;
+    }
+    let since = http_date::parse(request.headers.get(&HeaderName::IF_MODIFIED_SINCE)?)?;
+    // HTTP-date has no fractional seconds.
+    let modified = last_modified?.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    if UNIX_EPOCH + Duration::from_secs(modified) <= since {
+        Some(Status::NotModified)
+    } else {
+        None
+    }
+}
+
+/// Return the value of `Range` header if the ranges should be served.
+/// Ranges are served only for GET requests and only if `If-Range` matches the file.
+fn requested_ranges<'a>(
+    request: &'a Request,
+    etag: Option<&str>,
+    last_modified: Option<SystemTime>,
+) -> Option<&'a str> {
+    if request.method != Method::Get {
+        return None;
+    }
+    let value = request.headers.get(&HeaderName::RANGE)?;
+    let if_range = match request.headers.get(&HeaderName::IF_RANGE) {
+        Some(if_range) => if_range.trim(),
+        None => return Some(value),
+    };
+    let matches = if if_range.starts_with('"') || if_range.starts_with("W/") {
+        // Entity tags are compared with strong comparison, so weak ones never match.
+        etag == Some(if_range)
+    } else {
+        let modified = last_modified.and_then(|modified| modified.duration_since(UNIX_EPOCH).ok());
+        let date = http_date::parse(if_range).and_then(|date| date.duration_since(UNIX_EPOCH).ok());
+        match (modified, date) {
+            (Some(modified), Some(date)) => modified.as_secs() == date.as_secs(),
+            _ => false,
+        }
+    };
+    if matches {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+/// Respond 206 Partial Content with the ranges of the file.
+fn partial_response(
+    path: &Path,
+    ranges: &[ByteRange],
+    complete_length: u64,
+) -> io::Result<Response> {
+    let mut file = File::open(path)?;
+    let content_type = mime::from_path(path);
+    let mut response = Response::new(Status::PartialContent);
+    if let [range] = ranges {
+        file.seek(SeekFrom::Start(range.start))?;
+        response
+            .headers
+            .insert(HeaderName::CONTENT_TYPE, content_type.to_string());
+        response.headers.insert(
+            HeaderName::CONTENT_RANGE,
+            range.content_range(complete_length),
+        );
+        response.set_body(Body::from_reader(file, range.length()));
+    } else {
+        let boundary = format!("{:016x}", RandomState::new().build_hasher().finish());
+        response.headers.insert(
+            HeaderName::CONTENT_TYPE,
+            format!("multipart/byteranges; boundary={}", boundary),
+        );
+        response.set_body(range::multipart_body(
+            file,
+            ranges,
+            content_type,
+            complete_length,
+            &boundary,
+        ));
+    }
+    Ok(response)
+}
+
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn io_error_status(err: &io::Error) -> Status {
+    match err.kind() {
+        io::ErrorKind::NotFound | io::ErrorKind::NotADirectory => Status::NotFound,
+        io::ErrorKind::PermissionDenied => Status::Forbidden,
+        _ => Status::InternalServerError,
     }
 }
 
 impl Handler for StaticFiles {
     fn handle(&self, request: &Request) -> Response {
-        let current_dir = std::env::current_dir().unwrap();
-        let request_path = current_dir.join(&self.root);
-        dbg!(&request_path);
-        let request_path = request_path.join(&request.uri.trim_start_matches('/'));
-        dbg!(&request_path);
-        // Todo: remove unwrap() and return 404 instead.
-        let file = File::open(request_path).unwrap();
-        let response = file.to_response().unwrap();
-        response
+        // If mounted on a wildcard route such as `/assets/*`, the file path is relative to
+        // the route.
+        let file_path = request.wildcard().unwrap_or(&request.path);
+        let file_path = match self.resolve(file_path) {
+            Ok(file_path) => file_path,
+            Err(status) => return self.error_response(status),
+        };
+        if file_path.is_dir() {
+            return self.directory_response(request, &file_path);
+        }
+        self.file_response(request, &file_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::handler::Handler;
+    use crate::headers::HeaderName;
+    use crate::http_date;
+    use crate::request::Request;
+    use crate::response::Response;
+    use crate::server::Server;
+    use crate::static_files::{io_error_status, StaticFiles, SymlinkPolicy};
+    use crate::status::Status;
+    use crate::test_client::TestClient;
+    use std::fs;
+    use std::io;
+    use std::ops::Deref;
+    use std::path::{Path, PathBuf};
+
+    // Directory for a test under the temporary directory, which is removed on drop.
+    struct TempDir(PathBuf);
+
+    impl Deref for TempDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl AsRef<Path> for TempDir {
+        fn as_ref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // Create an empty directory for a test under the temporary directory.
+    fn temp_dir(name: &str) -> TempDir {
+        let dir =
+            std::env::temp_dir().join(format!("toy_http_server_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    #[test]
+    fn test_handle_wildcard() {
+        let root = temp_dir("wildcard");
+        fs::write(root.join("x.css"), "body {}").unwrap();
+        let mut request = Request::new(b"GET /assets/x.css HTTP/1.1\r\n\r\n").unwrap();
+        request.params.insert("*".to_string(), "x.css".to_string());
+        let response = StaticFiles::new(&root).handle(&request);
+        assert_eq!(
+            response.headers.get(&HeaderName::CONTENT_TYPE),
+            Some("text/css; charset=utf-8")
+        );
+        assert_eq!(response.body.into_bytes().unwrap(), b"body {}");
+    }
+
+    #[test]
+    fn test_handle_wildcard_route() {
+        let root = temp_dir("wildcard_route");
+        fs::create_dir_all(root.join("css")).unwrap();
+        fs::write(root.join("css").join("x.css"), "body {}").unwrap();
+        let client = TestClient::new(Server::new().route("/assets/*", StaticFiles::new(&root)));
+        let response = client.get("/assets/css/x.css");
+        assert_eq!(response.status(), Status::OK);
+        assert_eq!(
+            response.header(&HeaderName::CONTENT_TYPE),
+            Some("text/css; charset=utf-8")
+        );
+        assert_eq!(response.body(), b"body {}");
+        assert_eq!(client.get("/assets/missing.css").status(), Status::NotFound);
+        // Paths are resolved relative to the route, not to the root of the server.
+        assert_eq!(client.get("/css/x.css").status(), Status::NotFound);
+    }
+
+    fn get(static_files: &StaticFiles, path: &str) -> Response {
+        let request = format!("GET /{} HTTP/1.1\r\n\r\n", path.trim_start_matches('/'));
+        get_with(static_files, &request)
+    }
+
+    // Create a root directory to serve and a secret file next to it.
+    fn traversal_fixture(name: &str) -> (TempDir, StaticFiles) {
+        let dir = temp_dir(name);
+        fs::write(dir.join("secret.txt"), "secret").unwrap();
+        let root = dir.join("root");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("sub").join("a.txt"), "a").unwrap();
+        fs::write(root.join("index.html"), "index").unwrap();
+        (dir, StaticFiles::new(&root))
+    }
+
+    #[test]
+    fn test_handle_traversal() {
+        let (_dir, static_files) = traversal_fixture("traversal");
+        assert_eq!(get(&static_files, "sub/a.txt").status, Status::OK);
+        assert_eq!(get(&static_files, "./sub/%61.txt").status, Status::OK);
+        // `..` is allowed as long as the path stays inside of the root.
+        let response = get(&static_files, "sub/../index.html");
+        assert_eq!(response.status, Status::OK);
+        assert_eq!(response.body.into_bytes().unwrap(), b"index");
+        assert_eq!(
+            get(&static_files, "sub/%2e%2e/sub/a.txt").status,
+            Status::OK
+        );
+        assert_eq!(
+            get(&static_files, "sub/../../root/index.html").status,
+            Status::Forbidden
+        );
+
+        let payloads = [
+            "../secret.txt",
+            "/../secret.txt",
+            "sub/../../secret.txt",
+            "..%2fsecret.txt",
+            "..%2Fsecret.txt",
+            "%2e%2e/secret.txt",
+            "%2E%2E%2Fsecret.txt",
+            "sub/%2e%2e/%2e%2e/secret.txt",
+            "%2f..%2fsecret.txt",
+            "%2fetc%2fpasswd",
+            "..\\secret.txt",
+            "..%5csecret.txt",
+            "%252e%252e/secret.txt",
+            "..%00/secret.txt",
+            "index.html%00.txt",
+            "%c0%ae%c0%ae/secret.txt",
+            "%2",
+        ];
+        for payload in &payloads {
+            let response = get(&static_files, payload);
+            assert!(
+                matches!(
+                    response.status,
+                    Status::BadRequest | Status::Forbidden | Status::NotFound
+                ),
+                "{}: {}",
+                payload,
+                response.status
+            );
+        }
+        assert_eq!(
+            get(&static_files, "../secret.txt").status,
+            Status::Forbidden
+        );
+        assert_eq!(
+            get(&static_files, "%2e%2e/secret.txt").status,
+            Status::Forbidden
+        );
+        assert_eq!(
+            get(&static_files, "..%00/secret.txt").status,
+            Status::BadRequest
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_handle_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let (dir, static_files) = traversal_fixture("symlinks");
+        let root = dir.join("root");
+        symlink(dir.join("secret.txt"), root.join("outside.txt")).unwrap();
+        symlink(&*dir, root.join("outside_dir")).unwrap();
+        symlink(root.join("sub").join("a.txt"), root.join("inside.txt")).unwrap();
+
+        assert_eq!(get(&static_files, "inside.txt").status, Status::OK);
+        assert_eq!(get(&static_files, "outside.txt").status, Status::Forbidden);
+        assert_eq!(
+            get(&static_files, "outside_dir/secret.txt").status,
+            Status::Forbidden
+        );
+
+        let static_files = static_files.symlinks(SymlinkPolicy::Follow);
+        let response = get(&static_files, "outside.txt");
+        assert_eq!(response.body.into_bytes().unwrap(), b"secret");
+    }
+
+    #[test]
+    fn test_handle_not_found() {
+        let (_dir, static_files) = traversal_fixture("not_found");
+        assert_eq!(get(&static_files, "missing.txt").status, Status::NotFound);
+        assert_eq!(get(&static_files, "index.html/a").status, Status::NotFound);
+        assert_eq!(get(&static_files, "sub/").status, Status::NotFound);
+    }
+
+    #[test]
+    fn test_handle_error_body() {
+        let (_dir, static_files) = traversal_fixture("error_body");
+        let static_files = static_files
+            .error_body(Status::NotFound, "text/html", "<h1>Not Found</h1>")
+            .error_body(Status::Forbidden, "text/plain", "forbidden");
+
+        let response = get(&static_files, "missing.txt");
+        assert_eq!(response.status, Status::NotFound);
+        assert_eq!(
+            response.headers.get(&HeaderName::CONTENT_TYPE).unwrap(),
+            "text/html"
+        );
+        assert_eq!(response.body.into_bytes().unwrap(), b"<h1>Not Found</h1>");
+
+        let response = get(&static_files, "../secret.txt");
+        assert_eq!(response.status, Status::Forbidden);
+        assert_eq!(response.body.into_bytes().unwrap(), b"forbidden");
+
+        // Statuses without a configured body keep an empty one.
+        let response = get(&static_files, "%2");
+        assert_eq!(response.status, Status::BadRequest);
+        assert!(response.body.is_empty());
+    }
+
+    #[test]
+    fn test_io_error_status() {
+        let status = |kind| io_error_status(&io::Error::from(kind));
+        assert_eq!(status(io::ErrorKind::NotFound), Status::NotFound);
+        assert_eq!(status(io::ErrorKind::NotADirectory), Status::NotFound);
+        assert_eq!(status(io::ErrorKind::PermissionDenied), Status::Forbidden);
+        assert_eq!(
+            status(io::ErrorKind::Interrupted),
+            Status::InternalServerError
+        );
+        assert_eq!(
+            status(io::ErrorKind::InvalidData),
+            Status::InternalServerError
+        );
+    }
+
+    // Permission checks are skipped for root, so the assertion only runs as a normal user.
+    #[cfg(unix)]
+    #[test]
+    fn test_handle_permission_denied() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (dir, static_files) = traversal_fixture("permission_denied");
+        let path = dir.join("root").join("index.html");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o000)).unwrap();
+        if fs::File::open(&path).is_ok() {
+            eprintln!("skipped test_handle_permission_denied: files are readable regardless of permissions");
+            return;
+        }
+        assert_eq!(get(&static_files, "index.html").status, Status::Forbidden);
+    }
+
+    #[test]
+    fn test_handle_directory_index() {
+        let (dir, static_files) = traversal_fixture("directory_index");
+        fs::write(dir.join("root").join("sub").join("index.html"), "sub index").unwrap();
+
+        let response = get(&static_files, "");
+        assert_eq!(response.status, Status::OK);
+        assert_eq!(
+            response.headers.get(&HeaderName::CONTENT_TYPE).unwrap(),
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(response.body.into_bytes().unwrap(), b"index");
+        let response = get(&static_files, "sub/");
+        assert_eq!(response.body.into_bytes().unwrap(), b"sub index");
+    }
+
+    #[test]
+    fn test_handle_directory_redirect() {
+        let (dir, static_files) = traversal_fixture("directory_redirect");
+        let response = get(&static_files, "sub");
+        assert_eq!(response.status, Status::MovedPermanently);
+        assert_eq!(
+            response.headers.get(&HeaderName::LOCATION).unwrap(),
+            "/sub/"
+        );
+
+        // The URI of the request is kept when mounted on a prefix.
+        let mut request = Request::new(b"GET /assets/sub?a=1 HTTP/1.1\r\n\r\n").unwrap();
+        request.params.insert("*".to_string(), "sub".to_string());
+        let response = static_files.handle(&request);
+        assert_eq!(
+            response.headers.get(&HeaderName::LOCATION).unwrap(),
+            "/assets/sub/?a=1"
+        );
+
+        // A path starting with `//` must not redirect to another host.
+        for (target, location) in &[
+            ("//sub", "/sub/"),
+            ("///sub", "/sub/"),
+            ("/./sub", "/sub/"),
+            ("/sub/..//sub", "/sub/"),
+            ("/sub#x", "/sub/"),
+            ("/sub?q=a+b&flag#x", "/sub/?q=a%20b&flag="),
+        ] {
+            let request = format!("GET {} HTTP/1.1\r\n\r\n", target);
+            let response = get_with(&static_files, &request);
+            assert_eq!(response.status, Status::MovedPermanently, "{}", target);
+            assert_eq!(
+                response.headers.get(&HeaderName::LOCATION),
+                Some(*location),
+                "{}",
+                target
+            );
+        }
+        fs::create_dir(dir.join("root").join("a b")).unwrap();
+        let response = get(&static_files, "a%20b");
+        assert_eq!(response.headers.get(&HeaderName::LOCATION), Some("/a%20b/"));
+    }
+
+    #[test]
+    fn test_handle_autoindex() {
+        let (dir, static_files) = traversal_fixture("autoindex");
+        let sub = dir.join("root").join("sub");
+        fs::write(sub.join("<b>&.txt"), "12345").unwrap();
+        fs::create_dir(sub.join("nested dir")).unwrap();
+
+        assert_eq!(get(&static_files, "sub/").status, Status::NotFound);
+        let static_files = static_files.autoindex(true);
+        let response = get(&static_files, "sub/");
+        assert_eq!(response.status, Status::OK);
+        assert_eq!(
+            response.headers.get(&HeaderName::CONTENT_TYPE).unwrap(),
+            "text/html; charset=utf-8"
+        );
+        let html = String::from_utf8(response.body.into_bytes().unwrap()).unwrap();
+        assert!(html.contains("<title>Index of /sub/</title>"));
+        assert!(html.contains("<a href=\"../\">../</a>"));
+        assert!(html.contains("<a href=\"%3Cb%3E%26.txt\">&lt;b&gt;&amp;.txt</a></td><td>5</td>"));
+        assert!(html.contains("<a href=\"nested%20dir/\">nested dir/</a></td><td>-</td>"));
+        assert!(html.contains("<a href=\"a.txt\">a.txt</a></td><td>1</td>"));
+        assert!(html.contains(" GMT</td>"));
+        assert!(!html.contains("<b>"));
+        // Entries are sorted by name.
+        assert!(html.find("%3Cb%3E").unwrap() < html.find("a.txt").unwrap());
+
+        // `index.html` takes precedence and the root has no link to the parent.
+        let response = get(&static_files, "");
+        assert_eq!(response.body.into_bytes().unwrap(), b"index");
+        fs::remove_file(dir.join("root").join("index.html")).unwrap();
+        let response = get(&static_files, "");
+        let html = String::from_utf8(response.body.into_bytes().unwrap()).unwrap();
+        assert!(html.contains("<a href=\"sub/\">sub/</a>"));
+        assert!(!html.contains("../"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_autoindex_hides_outside_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let (dir, static_files) = traversal_fixture("autoindex_symlinks");
+        let root = dir.join("root");
+        fs::remove_file(root.join("index.html")).unwrap();
+        symlink(dir.join("secret.txt"), root.join("outside.txt")).unwrap();
+        symlink(root.join("sub").join("a.txt"), root.join("inside.txt")).unwrap();
+        symlink(root.join("missing.txt"), root.join("broken.txt")).unwrap();
+
+        let static_files = static_files.autoindex(true);
+        let response = get(&static_files, "");
+        assert_eq!(response.status, Status::OK);
+        let html = String::from_utf8(response.body.into_bytes().unwrap()).unwrap();
+        assert!(html.contains("inside.txt"));
+        assert!(!html.contains("outside.txt"));
+        assert!(!html.contains("broken.txt"));
+
+        // Broken links are omitted even if links outside of the root are followed.
+        let static_files = static_files.symlinks(SymlinkPolicy::Follow);
+        let html = String::from_utf8(get(&static_files, "").body.into_bytes().unwrap()).unwrap();
+        assert!(html.contains("outside.txt"));
+        assert!(!html.contains("broken.txt"));
+    }
+
+    // Send the request through the router as the server does.
+    fn get_with(static_files: &StaticFiles, request: &str) -> Response {
+        let client = TestClient::new(Server::new().route("/*", static_files.clone()));
+        client.send_raw(request.as_bytes()).into_response()
+    }
+
+    #[test]
+    fn test_handle_validators() {
+        let (_dir, static_files) = traversal_fixture("validators");
+        let response = get(&static_files, "sub/a.txt");
+        let etag = response.headers.get(&HeaderName::ETAG).unwrap();
+        assert!(etag.starts_with("\"1-") && etag.ends_with('"'));
+        let last_modified = response.headers.get(&HeaderName::LAST_MODIFIED).unwrap();
+        assert!(http_date::parse(last_modified).is_some());
+        // The entity tag is stable while the file is unchanged.
+        assert_eq!(
+            get(&static_files, "sub/a.txt")
+                .headers
+                .get(&HeaderName::ETAG),
+            Some(etag)
+        );
+    }
+
+    #[test]
+    fn test_handle_if_none_match() {
+        let (_dir, static_files) = traversal_fixture("if_none_match");
+        let etag = get(&static_files, "sub/a.txt").headers[&HeaderName::ETAG].to_string();
+
+        let if_none_match = [
+            etag.clone(),
+            format!("W/{}", etag),
+            format!("\"other\", {}", etag),
+            "*".to_string(),
+        ];
+        for value in &if_none_match {
+            let request = format!(
+                "GET /sub/a.txt HTTP/1.1\r\nIf-None-Match: {}\r\n\r\n",
+                value
+            );
+            let response = get_with(&static_files, &request);
+            assert_eq!(response.status, Status::NotModified, "{}", value);
+            assert_eq!(response.headers.get(&HeaderName::ETAG), Some(etag.as_str()));
+            assert!(response.body.is_empty());
+        }
+
+        let request = "GET /sub/a.txt HTTP/1.1\r\nIf-None-Match: \"other\"\r\n\r\n";
+        let response = get_with(&static_files, request);
+        assert_eq!(response.status, Status::OK);
+        assert_eq!(response.body.into_bytes().unwrap(), b"a");
+
+        // `If-Modified-Since` is ignored if `If-None-Match` is present.
+        let request = concat!(
+            "GET /sub/a.txt HTTP/1.1\r\n",
+            "If-None-Match: \"other\"\r\n",
+            "If-Modified-Since: Thu, 31 Dec 2099 23:59:59 GMT\r\n\r\n"
+        );
+        assert_eq!(get_with(&static_files, request).status, Status::OK);
+
+        let request = "DELETE /sub/a.txt HTTP/1.1\r\nIf-None-Match: *\r\n\r\n";
+        assert_eq!(
+            get_with(&static_files, request).status,
+            Status::PreconditionFailed
+        );
+    }
+
+    #[test]
+    fn test_handle_if_modified_since() {
+        let (_dir, static_files) = traversal_fixture("if_modified_since");
+        let last_modified =
+            get(&static_files, "sub/a.txt").headers[&HeaderName::LAST_MODIFIED].to_string();
+
+        let request = format!(
+            "GET /sub/a.txt HTTP/1.1\r\nIf-Modified-Since: {}\r\n\r\n",
+            last_modified
+        );
+        let response = get_with(&static_files, &request);
+        assert_eq!(response.status, Status::NotModified);
+        assert_eq!(
+            response.headers.get(&HeaderName::LAST_MODIFIED),
+            Some(last_modified.as_str())
+        );
+        assert!(response.body.is_empty());
+
+        let dates = ["Thu, 01 Jan 1970 00:00:00 GMT", "invalid date"];
+        for date in &dates {
+            let request = format!(
+                "GET /sub/a.txt HTTP/1.1\r\nIf-Modified-Since: {}\r\n\r\n",
+                date
+            );
+            assert_eq!(get_with(&static_files, &request).status, Status::OK);
+        }
+
+        // Only GET and HEAD requests are conditional on modification time.
+        let request = format!(
+            "POST /sub/a.txt HTTP/1.1\r\nIf-Modified-Since: {}\r\n\r\n",
+            last_modified
+        );
+        assert_eq!(get_with(&static_files, &request).status, Status::OK);
+    }
+
+    fn range_fixture(name: &str) -> (TempDir, StaticFiles) {
+        let (dir, static_files) = traversal_fixture(name);
+        fs::write(dir.join("root").join("digits.txt"), "0123456789").unwrap();
+        (dir, static_files)
+    }
+
+    fn get_range(static_files: &StaticFiles, headers: &str) -> Response {
+        let request = format!("GET /digits.txt HTTP/1.1\r\n{}\r\n", headers);
+        get_with(static_files, &request)
+    }
+
+    #[test]
+    fn test_handle_single_range() {
+        let (_dir, static_files) = range_fixture("single_range");
+        let response = get(&static_files, "digits.txt");
+        assert_eq!(response.status, Status::OK);
+        assert_eq!(&response.headers[&HeaderName::ACCEPT_RANGES], "bytes");
+
+        let ranges = [
+            ("bytes=2-4", "bytes 2-4/10", &b"234"[..]),
+            ("bytes=7-", "bytes 7-9/10", b"789"),
+            ("bytes=-2", "bytes 8-9/10", b"89"),
+            ("bytes=8-100", "bytes 8-9/10", b"89"),
+        ];
+        for (range, content_range, body) in &ranges {
+            let response = get_range(&static_files, &format!("Range: {}\r\n", range));
+            assert_eq!(response.status, Status::PartialContent, "{}", range);
+            assert_eq!(
+                &response.headers[&HeaderName::CONTENT_RANGE],
+                *content_range
+            );
+            assert_eq!(
+                &response.headers[&HeaderName::CONTENT_TYPE],
+                "text/plain; charset=utf-8"
+            );
+            assert_eq!(&response.headers[&HeaderName::ACCEPT_RANGES], "bytes");
+            assert_eq!(
+                &response.headers[&HeaderName::CONTENT_LENGTH],
+                body.len().to_string()
+            );
+            assert_eq!(response.body.into_bytes().unwrap(), *body);
+        }
+    }
+
+    #[test]
+    fn test_handle_multiple_ranges() {
+        let (_dir, static_files) = range_fixture("multiple_ranges");
+        let response = get_range(&static_files, "Range: bytes=0-1, -3\r\n");
+        assert_eq!(response.status, Status::PartialContent);
+        let content_type = &response.headers[&HeaderName::CONTENT_TYPE];
+        let boundary = content_type
+            .strip_prefix("multipart/byteranges; boundary=")
+            .unwrap()
+            .to_string();
+        let content_length: u64 = response.headers[&HeaderName::CONTENT_LENGTH]
+            .parse()
+            .unwrap();
+        let body = String::from_utf8(response.body.into_bytes().unwrap()).unwrap();
+        let expected = format!(
+            concat!(
+                "--{0}\r\nContent-Type: text/plain; charset=utf-8\r\n",
+                "Content-Range: bytes 0-1/10\r\n\r\n01\r\n",
+                "--{0}\r\nContent-Type: text/plain; charset=utf-8\r\n",
+                "Content-Range: bytes 7-9/10\r\n\r\n789\r\n",
+                "--{0}--\r\n"
+            ),
+            boundary
+        );
+        assert_eq!(body, expected);
+        assert_eq!(content_length, body.len() as u64);
+    }
+
+    #[test]
+    fn test_handle_overlapping_ranges() {
+        let (_dir, static_files) = range_fixture("overlapping_ranges");
+        // Repeated ranges must not make the file sent many times.
+        let value = vec!["0-"; 64].join(",");
+        let response = get_range(&static_files, &format!("Range: bytes={}\r\n", value));
+        assert_eq!(response.status, Status::PartialContent);
+        assert_eq!(
+            response.headers.get(&HeaderName::CONTENT_RANGE),
+            Some("bytes 0-9/10")
+        );
+        assert_eq!(response.body.into_bytes().unwrap(), b"0123456789");
+    }
+
+    #[test]
+    fn test_handle_unsatisfiable_range() {
+        let (_dir, static_files) = range_fixture("unsatisfiable_range");
+        let response = get_range(&static_files, "Range: bytes=10-\r\n");
+        assert_eq!(response.status, Status::RangeNotSatisfiable);
+        assert_eq!(&response.headers[&HeaderName::CONTENT_RANGE], "bytes */10");
+        assert!(response.body.is_empty());
+
+        // Malformed ranges and ranges of other methods are ignored.
+        let response = get_range(&static_files, "Range: bytes=5-1\r\n");
+        assert_eq!(response.status, Status::OK);
+        assert_eq!(response.body.into_bytes().unwrap(), b"0123456789");
+        let request = "HEAD /digits.txt HTTP/1.1\r\nRange: bytes=0-1\r\n\r\n";
+        assert_eq!(get_with(&static_files, request).status, Status::OK);
+    }
+
+    #[test]
+    fn test_handle_if_range() {
+        let (_dir, static_files) = range_fixture("if_range");
+        let response = get(&static_files, "digits.txt");
+        let etag = response.headers[&HeaderName::ETAG].to_string();
+        let last_modified = response.headers[&HeaderName::LAST_MODIFIED].to_string();
+
+        let partial = [etag.clone(), last_modified];
+        for if_range in &partial {
+            let headers = format!("Range: bytes=0-0\r\nIf-Range: {}\r\n", if_range);
+            let response = get_range(&static_files, &headers);
+            assert_eq!(response.status, Status::PartialContent, "{}", if_range);
+            assert_eq!(response.body.into_bytes().unwrap(), b"0");
+        }
+
+        let full = [
+            "\"other\"".to_string(),
+            format!("W/{}", etag),
+            "Thu, 01 Jan 1970 00:00:00 GMT".to_string(),
+            "invalid date".to_string(),
+        ];
+        for if_range in &full {
+            let headers = format!("Range: bytes=0-0\r\nIf-Range: {}\r\n", if_range);
+            let response = get_range(&static_files, &headers);
+            assert_eq!(response.status, Status::OK, "{}", if_range);
+            assert_eq!(response.body.into_bytes().unwrap(), b"0123456789");
+        }
     }
 }
@@ -1,15 +1,257 @@
-use std::convert::Into;
+use std::error::Error;
+use std::fmt;
 
-/// Enum of response statuses. Each status is converted into status code and message
-/// through `into()` function.
-pub enum Status {
-    OK,
+macro_rules! statuses {
+    ($($variant:ident => ($code:expr, $reason_phrase:expr),)*) => {
+        /// Enum of response statuses registered in IANA HTTP Status Code Registry.
+        /// Other statuses are represented by `Custom`, which is created by `Status::custom`.
+        #[derive(Clone, Debug, Eq, Hash, PartialEq)]
+        pub enum Status {
+            $($variant,)*
+            Custom(CustomStatus),
+        }
+
+        impl Status {
+            pub fn code(&self) -> u16 {
+                match self {
+                    $(Status::$variant => $code,)*
+                    Status::Custom(custom) => custom.code,
+                }
+            }
+
+            pub fn reason_phrase(&self) -> &str {
+                match self {
+                    $(Status::$variant => $reason_phrase,)*
+                    Status::Custom(custom) => &custom.reason_phrase,
+                }
+            }
+
+            /// Return the registered status for the code, or `None` if the code is not
+            /// registered.
+            pub fn from_u16(code: u16) -> Option<Status> {
+                match code {
+                    $($code => Some(Status::$variant),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+statuses! {
+    // 1xx Informational
+    Continue => (100, "Continue"),
+    SwitchingProtocols => (101, "Switching Protocols"),
+    Processing => (102, "Processing"),
+    EarlyHints => (103, "Early Hints"),
+    // 2xx Success
+    OK => (200, "OK"),
+    Created => (201, "Created"),
+    Accepted => (202, "Accepted"),
+    NonAuthoritativeInformation => (203, "Non-Authoritative Information"),
+    NoContent => (204, "No Content"),
+    ResetContent => (205, "Reset Content"),
+    PartialContent => (206, "Partial Content"),
+    MultiStatus => (207, "Multi-Status"),
+    AlreadyReported => (208, "Already Reported"),
+    IMUsed => (226, "IM Used"),
+    // 3xx Redirection
+    MultipleChoices => (300, "Multiple Choices"),
+    MovedPermanently => (301, "Moved Permanently"),
+    Found => (302, "Found"),
+    SeeOther => (303, "See Other"),
+    NotModified => (304, "Not Modified"),
+    UseProxy => (305, "Use Proxy"),
+    TemporaryRedirect => (307, "Temporary Redirect"),
+    PermanentRedirect => (308, "Permanent Redirect"),
+    // 4xx Client Error
+    BadRequest => (400, "Bad Request"),
+    Unauthorized => (401, "Unauthorized"),
+    PaymentRequired => (402, "Payment Required"),
+    Forbidden => (403, "Forbidden"),
+    NotFound => (404, "Not Found"),
+    MethodNotAllowed => (405, "Method Not Allowed"),
+    NotAcceptable => (406, "Not Acceptable"),
+    ProxyAuthenticationRequired => (407, "Proxy Authentication Required"),
+    RequestTimeout => (408, "Request Timeout"),
+    Conflict => (409, "Conflict"),
+    Gone => (410, "Gone"),
+    LengthRequired => (411, "Length Required"),
+    PreconditionFailed => (412, "Precondition Failed"),
+    PayloadTooLarge => (413, "Payload Too Large"),
+    URITooLong => (414, "URI Too Long"),
+    UnsupportedMediaType => (415, "Unsupported Media Type"),
+    RangeNotSatisfiable => (416, "Range Not Satisfiable"),
+    ExpectationFailed => (417, "Expectation Failed"),
+    MisdirectedRequest => (421, "Misdirected Request"),
+    UnprocessableEntity => (422, "Unprocessable Entity"),
+    Locked => (423, "Locked"),
+    FailedDependency => (424, "Failed Dependency"),
+    TooEarly => (425, "Too Early"),
+    UpgradeRequired => (426, "Upgrade Required"),
+    PreconditionRequired => (428, "Precondition Required"),
+    TooManyRequests => (429, "Too Many Requests"),
+    RequestHeaderFieldsTooLarge => (431, "Request Header Fields Too Large"),
+    UnavailableForLegalReasons => (451, "Unavailable For Legal Reasons"),
+    // 5xx Server Error
+    InternalServerError => (500, "Internal Server Error"),
+    NotImplemented => (501, "Not Implemented"),
+    BadGateway => (502, "Bad Gateway"),
+    ServiceUnavailable => (503, "Service Unavailable"),
+    GatewayTimeout => (504, "Gateway Timeout"),
+    HTTPVersionNotSupported => (505, "HTTP Version Not Supported"),
+    VariantAlsoNegotiates => (506, "Variant Also Negotiates"),
+    InsufficientStorage => (507, "Insufficient Storage"),
+    LoopDetected => (508, "Loop Detected"),
+    NotExtended => (510, "Not Extended"),
+    NetworkAuthenticationRequired => (511, "Network Authentication Required"),
+}
+
+/// Status code and reason phrase of a status which is not registered.
+/// Its fields are private so that the status is validated by `Status::custom`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct CustomStatus {
+    code: u16,
+    reason_phrase: String,
 }
 
-impl Into<(u16, String)> for Status {
-    fn into(self) -> (u16, String) {
+#[derive(Debug, Eq, PartialEq)]
+pub enum StatusError {
+    /// The status code is out of 100 to 599.
+    InvalidCode(u16),
+    /// The reason phrase has control characters such as CR and LF.
+    InvalidReasonPhrase,
+}
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Status::OK => (200, "OK".to_string()),
+            StatusError::InvalidCode(code) => write!(f, "Invalid status code: {}", code),
+            StatusError::InvalidReasonPhrase => write!(f, "Invalid reason phrase"),
         }
     }
 }
+
+impl Error for StatusError {}
+
+impl Status {
+    /// Create a status from the code and reason phrase. Registered codes are mapped to
+    /// their variants regardless of the reason phrase.
+    pub fn custom<S: Into<String>>(code: u16, reason_phrase: S) -> Result<Status, StatusError> {
+        if !(100..600).contains(&code) {
+            return Err(StatusError::InvalidCode(code));
+        }
+        if let Some(status) = Status::from_u16(code) {
+            return Ok(status);
+        }
+        let reason_phrase = reason_phrase.into();
+        // Control characters such as CR and LF would end the status line.
+        if reason_phrase.chars().any(|c| c != '\t' && c.is_control()) {
+            return Err(StatusError::InvalidReasonPhrase);
+        }
+        Ok(Status::Custom(CustomStatus {
+            code,
+            reason_phrase,
+        }))
+    }
+
+    pub fn is_informational(&self) -> bool {
+        (100..200).contains(&self.code())
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.code())
+    }
+
+    pub fn is_redirection(&self) -> bool {
+        (300..400).contains(&self.code())
+    }
+
+    pub fn is_client_error(&self) -> bool {
+        (400..500).contains(&self.code())
+    }
+
+    pub fn is_server_error(&self) -> bool {
+        (500..600).contains(&self.code())
+    }
+}
+
+/// Format as status code and reason phrase in status line, such as "404 Not Found".
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.code(), self.reason_phrase())
+    }
+}
+
+impl From<Status> for (u16, String) {
+    fn from(status: Status) -> Self {
+        (status.code(), status.reason_phrase().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::status::{Status, StatusError};
+
+    #[test]
+    fn test_from_u16() {
+        for code in 0..1000 {
+            if let Some(status) = Status::from_u16(code) {
+                assert_eq!(status.code(), code);
+            }
+        }
+        assert_eq!(Status::from_u16(404), Some(Status::NotFound));
+        assert_eq!(Status::from_u16(306), None);
+        assert_eq!(Status::from_u16(999), None);
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Status::OK.to_string(), "200 OK");
+        assert_eq!(
+            Status::HTTPVersionNotSupported.to_string(),
+            "505 HTTP Version Not Supported"
+        );
+        let custom = Status::custom(599, "Network Connect Timeout").unwrap();
+        assert_eq!(custom.to_string(), "599 Network Connect Timeout");
+    }
+
+    #[test]
+    fn test_classes() {
+        assert!(Status::Continue.is_informational());
+        assert!(Status::NoContent.is_success());
+        assert!(Status::MovedPermanently.is_redirection());
+        assert!(Status::NotFound.is_client_error());
+        assert!(!Status::NotFound.is_server_error());
+        assert!(Status::BadGateway.is_server_error());
+        assert!(Status::custom(499, "Client Closed Request")
+            .unwrap()
+            .is_client_error());
+    }
+
+    #[test]
+    fn test_custom() {
+        let custom = Status::custom(499, "Client Closed Request").unwrap();
+        assert_eq!(custom.code(), 499);
+        assert_eq!(custom.reason_phrase(), "Client Closed Request");
+        assert_eq!(Status::custom(200, "Fine"), Ok(Status::OK));
+        assert_eq!(Status::custom(404, "Not Found"), Ok(Status::NotFound));
+        assert_eq!(
+            Status::custom(99, "Too Small"),
+            Err(StatusError::InvalidCode(99))
+        );
+        assert_eq!(
+            Status::custom(600, "Too Large"),
+            Err(StatusError::InvalidCode(600))
+        );
+        assert_eq!(
+            Status::custom(499, "Closed\r\nSet-Cookie: a=b"),
+            Err(StatusError::InvalidReasonPhrase)
+        );
+        assert_eq!(
+            Status::custom(499, "Closed\0"),
+            Err(StatusError::InvalidReasonPhrase)
+        );
+        assert!(Status::custom(499, "Closed\tRequest").is_ok());
+    }
+}
@@ -0,0 +1,251 @@
+use crate::headers::HeaderName;
+use crate::request::{Method, Request};
+use crate::response::{Body, Response};
+use crate::router::Router;
+use crate::server::Server;
+use crate::status::Status;
+
+/// Client to test handlers without sockets. Requests are dispatched to handlers through
+/// the same routing as `Server::run`.
+pub struct TestClient {
+    server: Server,
+}
+
+impl TestClient {
+    pub fn new(server: Server) -> Self {
+        Self { server }
+    }
+
+    pub fn from_router(router: Router) -> Self {
+        Self::new(Server::new().router(router))
+    }
+
+    /// Send the request and return the response, which has `Connection` and
+    /// `Content-Length` headers as `Server::run` writes.
+    pub fn send(&self, mut request: Request) -> TestResponse {
+        let keep_alive = request.keep_alive();
+        let mut response = self.server.respond(&mut request);
+        Server::finalize(&request, &mut response, keep_alive);
+        let body = std::mem::take(&mut response.body)
+            .into_bytes()
+            .expect("Failed to read the body");
+        TestResponse { response, body }
+    }
+
+    /// Parse the raw request and send it. Requests which cannot be parsed are responded
+    /// with an error status such as 400 Bad Request as `Server::run` does.
+    pub fn send_raw(&self, request: &[u8]) -> TestResponse {
+        match Request::new(request) {
+            Ok(request) => self.send(request),
+            Err(err) => {
+                let mut response = Server::error_response(&*err).expect("Invalid request");
+                Server::set_connection(&mut response, None);
+                response.set_content_length();
+                TestResponse {
+                    response,
+                    body: Vec::new(),
+                }
+            }
+        }
+    }
+
+    /// Send a request without body. The request has only `Content-Length: 0` header.
+    pub fn request(&self, method: Method, uri: &str) -> TestResponse {
+        self.request_with_body(method, uri, &[])
+    }
+
+    /// Send a request with the body and `Content-Length` header.
+    pub fn request_with_body(&self, method: Method, uri: &str, body: &[u8]) -> TestResponse {
+        let mut request = format!(
+            "{} {} HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            method,
+            uri,
+            body.len()
+        )
+        .into_bytes();
+        request.extend_from_slice(body);
+        self.send_raw(&request)
+    }
+
+    pub fn get(&self, uri: &str) -> TestResponse {
+        self.request(Method::Get, uri)
+    }
+
+    pub fn post(&self, uri: &str, body: &[u8]) -> TestResponse {
+        self.request_with_body(Method::Post, uri, body)
+    }
+
+    pub fn put(&self, uri: &str, body: &[u8]) -> TestResponse {
+        self.request_with_body(Method::Put, uri, body)
+    }
+
+    pub fn delete(&self, uri: &str) -> TestResponse {
+        self.request(Method::Delete, uri)
+    }
+}
+
+/// Response returned by `TestClient`.
+#[derive(Debug)]
+pub struct TestResponse {
+    response: Response,
+    body: Vec<u8>,
+}
+
+impl TestResponse {
+    pub fn status(&self) -> Status {
+        self.response.status.clone()
+    }
+
+    pub fn header(&self, header_name: &HeaderName) -> Option<&str> {
+        self.response.headers.get(header_name)
+    }
+
+    /// Return the body, which is empty if the response has no body.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Return the body as a string. Invalid UTF-8 sequences are replaced.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(self.body()).into_owned()
+    }
+
+    pub fn into_response(self) -> Response {
+        let mut response = self.response;
+        response.body = Body::Bytes(self.body);
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::headers::HeaderName;
+    use crate::request::{Method, Request};
+    use crate::response::Response;
+    use crate::router::Router;
+    use crate::server::Server;
+    use crate::status::Status;
+    use crate::test_client::TestClient;
+
+    fn echo_handler(request: &Request) -> Response {
+        let mut response = Response::new(Status::OK);
+        let id = request.param("id").unwrap_or("");
+        let body = String::from_utf8(request.body.clone()).unwrap();
+        response.set_body(format!("{} {}", id, body));
+        response
+    }
+
+    fn client() -> TestClient {
+        TestClient::new(
+            Server::new()
+                .get("/users/:id", echo_handler)
+                .post("/users/:id", echo_handler),
+        )
+    }
+
+    #[test]
+    fn test_get() {
+        let response = client().get("/users/1");
+        assert_eq!(response.status(), Status::OK);
+        assert_eq!(response.text(), "1 ");
+        assert_eq!(response.header(&HeaderName::CONTENT_LENGTH), Some("2"));
+    }
+
+    #[test]
+    fn test_post() {
+        let response = client().post("/users/2", b"name=foo");
+        assert_eq!(response.status(), Status::OK);
+        assert_eq!(response.body(), b"2 name=foo");
+    }
+
+    #[test]
+    fn test_not_found_and_method_not_allowed() {
+        let client = client();
+        assert_eq!(client.get("/unknown").status(), Status::NotFound);
+        let response = client.delete("/users/1");
+        assert_eq!(response.status(), Status::MethodNotAllowed);
+        assert_eq!(response.header(&HeaderName::ALLOW), Some("GET, HEAD, POST"));
+    }
+
+    #[test]
+    fn test_send() {
+        let request = Request::new(b"PROPFIND /users/3 HTTP/1.1\r\n\r\n").unwrap();
+        let response = client().send(request);
+        assert_eq!(response.status(), Status::MethodNotAllowed);
+    }
+
+    #[test]
+    fn test_from_router() {
+        let mut router = Router::new();
+        router.add_method_route(Method::Put, "/users/:id", echo_handler);
+        let client = TestClient::from_router(router);
+        assert_eq!(client.put("/users/4", b"x").text(), "4 x");
+        assert!(client.get("/users/4").body().is_empty());
+    }
+
+    #[test]
+    fn test_send_head_and_connection() {
+        let client = TestClient::new(Server::new().route("/users/:id", echo_handler));
+        let response = client.request(Method::Head, "/users/1");
+        assert_eq!(response.status(), Status::OK);
+        assert_eq!(response.header(&HeaderName::CONTENT_LENGTH), Some("2"));
+        assert!(response.body().is_empty());
+        assert_eq!(response.header(&HeaderName::CONNECTION), None);
+
+        let response = client.send_raw(b"GET /users/1 HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert_eq!(response.header(&HeaderName::CONNECTION), Some("close"));
+        let response = client.send_raw(b"GET /users/1 HTTP/1.0\r\nConnection: keep-alive\r\n\r\n");
+        assert_eq!(response.header(&HeaderName::CONNECTION), Some("keep-alive"));
+        let response = client.send_raw(b"GET /users/%zz HTTP/1.1\r\n\r\n");
+        assert_eq!(response.header(&HeaderName::CONNECTION), Some("close"));
+    }
+
+    #[test]
+    fn test_send_head_with_get_handler() {
+        let response = client().request(Method::Head, "/users/1");
+        assert_eq!(response.status(), Status::OK);
+        assert_eq!(response.header(&HeaderName::CONTENT_LENGTH), Some("2"));
+        assert!(response.body().is_empty());
+    }
+
+    #[test]
+    fn test_send_raw_with_connect_and_options() {
+        let client = client();
+        let response = client.send_raw(b"CONNECT example.com:443 HTTP/1.1\r\n\r\n");
+        assert_eq!(response.status(), Status::NotFound);
+        let response = client.send_raw(b"OPTIONS * HTTP/1.1\r\n\r\n");
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn test_route_with_query() {
+        let query_handler = |request: &Request| {
+            let mut response = Response::new(Status::OK);
+            let tags = request.query_all("tag").collect::<Vec<&str>>().join(",");
+            response.set_body(format!("{} {}", request.param("id").unwrap_or(""), tags));
+            response
+        };
+        let client = TestClient::new(Server::new().get("/users/:id", query_handler));
+        assert_eq!(client.get("/users/5?tag=a&tag=b%20c").text(), "5 a,b c");
+        // Parameters are matched against the decoded path.
+        assert_eq!(client.get("/users/%E3%81%82?").text(), "あ ");
+    }
+
+    #[test]
+    fn test_send_raw_with_invalid_request() {
+        let client = client();
+        for target in &["/users/%zz", "/users/1?page=%2"] {
+            let request = format!("GET {} HTTP/1.1\r\n\r\n", target);
+            let response = client.send_raw(request.as_bytes());
+            assert_eq!(response.status(), Status::BadRequest, "{}", target);
+        }
+        let response = client.send_raw(b"GET /users/1 HTTP/1.1\r\nHost localhost\r\n\r\n");
+        assert_eq!(response.status(), Status::BadRequest);
+        assert_eq!(response.header(&HeaderName::CONTENT_LENGTH), Some("0"));
+        assert_eq!(response.header(&HeaderName::CONNECTION), Some("close"));
+        let response = client.send_raw(
+            b"POST /users/1 HTTP/1.1\r\nContent-Length: 0\r\nContent-Length: 5\r\n\r\nhello",
+        );
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+}
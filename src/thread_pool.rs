@@ -0,0 +1,158 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Fixed number of threads which execute jobs sent through a channel.
+pub struct ThreadPool {
+    workers: Vec<Worker>,
+    sender: Option<mpsc::Sender<Job>>,
+}
+
+impl ThreadPool {
+    /// Create a pool with `size` threads.
+    /// Panics if `size` is zero.
+    pub fn new(size: usize) -> Self {
+        assert!(size > 0, "Thread pool needs at least one thread");
+        let (sender, receiver) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..size)
+            .map(|id| Worker::new(id, Arc::clone(&receiver)))
+            .collect();
+        Self {
+            workers,
+            sender: Some(sender),
+        }
+    }
+
+    /// Execute the job on one of idle threads. If all threads are busy, the job waits
+    /// until one of them finishes its job.
+    pub fn execute<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        if let Some(sender) = &self.sender {
+            sender
+                .send(Box::new(job))
+                .expect("Worker threads are terminated");
+        }
+    }
+}
+
+impl ThreadPool {
+    /// Stop receiving jobs and wait for threads to finish jobs already sent until the
+    /// timeout expires. Threads still running after the timeout are detached.
+    /// Return `true` if all threads finished in time.
+    pub fn join_timeout(mut self, timeout: Duration) -> bool {
+        drop(self.sender.take());
+        let deadline = Instant::now() + timeout;
+        while self.workers.iter().any(|worker| !worker.is_finished()) {
+            if Instant::now() >= deadline {
+                // Dropping `JoinHandle` detaches the thread.
+                self.workers.clear();
+                return false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        true
+    }
+}
+
+impl Drop for ThreadPool {
+    /// Wait for all threads to finish jobs already sent.
+    fn drop(&mut self) {
+        // Closing the channel stops workers after they run out of jobs.
+        drop(self.sender.take());
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                if thread.join().is_err() {
+                    eprintln!("Worker {} panicked", worker.id);
+                }
+            }
+        }
+    }
+}
+
+struct Worker {
+    id: usize,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Worker {
+    fn is_finished(&self) -> bool {
+        self.thread
+            .as_ref()
+            .is_none_or(|thread| thread.is_finished())
+    }
+
+    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Self {
+        let thread = thread::spawn(move || loop {
+            // The lock is released as soon as a job is received.
+            let job = receiver.lock().unwrap().recv();
+            match job {
+                Ok(job) => {
+                    // A panic in a job must not decrease the number of threads.
+                    if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                        eprintln!("Job panicked on worker {}", id);
+                    }
+                }
+                Err(_) => break,
+            }
+        });
+        Self {
+            id,
+            thread: Some(thread),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::thread_pool::ThreadPool;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_execute() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let pool = ThreadPool::new(4);
+        for _ in 0..100 {
+            let counter = Arc::clone(&counter);
+            pool.execute(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        // Dropping the pool waits for all jobs.
+        drop(pool);
+        assert_eq!(counter.load(Ordering::SeqCst), 100);
+    }
+
+    #[test]
+    fn test_execute_after_panic() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let pool = ThreadPool::new(1);
+        pool.execute(|| panic!("job panicked"));
+        let cloned = Arc::clone(&counter);
+        pool.execute(move || {
+            cloned.fetch_add(1, Ordering::SeqCst);
+        });
+        drop(pool);
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_join_timeout() {
+        let pool = ThreadPool::new(2);
+        pool.execute(|| thread::sleep(Duration::from_millis(50)));
+        assert!(pool.join_timeout(Duration::from_secs(5)));
+
+        let pool = ThreadPool::new(2);
+        pool.execute(|| thread::sleep(Duration::from_secs(5)));
+        assert!(!pool.join_timeout(Duration::from_millis(50)));
+    }
+}
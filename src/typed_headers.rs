@@ -0,0 +1,786 @@
+use crate::headers::{HeaderMap, HeaderName};
+use crate::http_date;
+use crate::range::{self, RangeSpec};
+use crate::request::is_token;
+use std::error::Error;
+use std::fmt;
+use std::time::SystemTime;
+
+/// Header whose values can be decoded into a structured value and encoded back.
+pub trait TypedHeader: Sized {
+    const NAME: HeaderName;
+
+    /// Decode all the values of the header, which are not empty.
+    fn decode(values: &[&str]) -> Result<Self, TypedHeaderError>;
+
+    /// Encode the header into a single value.
+    fn encode(&self) -> String;
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TypedHeaderError {
+    Missing(HeaderName),
+    Invalid(HeaderName),
+}
+
+impl fmt::Display for TypedHeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypedHeaderError::Missing(name) => write!(f, "Header {} is missing", name),
+            TypedHeaderError::Invalid(name) => write!(f, "Header {} is invalid", name),
+        }
+    }
+}
+
+impl Error for TypedHeaderError {}
+
+impl HeaderMap {
+    /// Decode the values of the typed header.
+    pub fn typed_get<H: TypedHeader>(&self) -> Result<H, TypedHeaderError> {
+        let name = H::NAME;
+        let values = self.get_all(&name).collect::<Vec<&str>>();
+        if values.is_empty() {
+            return Err(TypedHeaderError::Missing(name));
+        }
+        H::decode(&values)
+    }
+
+    /// Set the encoded typed header, replacing existing values of it. The encoded value
+    /// must not contain CR, LF or NUL, which would let it inject other headers.
+    pub fn typed_insert<H: TypedHeader>(&mut self, header: &H) -> Result<(), TypedHeaderError> {
+        let value = header.encode();
+        if value.contains(['\r', '\n', '\0']) {
+            return Err(invalid::<H>());
+        }
+        self.insert(H::NAME, value);
+        Ok(())
+    }
+}
+
+fn invalid<H: TypedHeader>() -> TypedHeaderError {
+    TypedHeaderError::Invalid(H::NAME)
+}
+
+/// Return the only value of a header which must not be repeated.
+fn single<'a, H: TypedHeader>(values: &[&'a str]) -> Result<&'a str, TypedHeaderError> {
+    match values {
+        [value] => Ok(value.trim()),
+        _ => Err(invalid::<H>()),
+    }
+}
+
+/// Split the string by the separator except for the ones in quoted strings.
+fn split_unquoted(s: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            _ if c == separator && !in_quotes => {
+                parts.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+/// Parse a token or a quoted-string such as `"a \"b\""`.
+fn parse_word(s: &str) -> Option<String> {
+    if is_token(s) {
+        return Some(s.to_string());
+    }
+    let inner = s.strip_prefix('"')?.strip_suffix('"')?;
+    let mut word = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => word.push(chars.next()?),
+            '"' => return None,
+            _ => word.push(c),
+        }
+    }
+    Some(word)
+}
+
+/// Format the word as a token if possible, or as a quoted-string.
+fn format_word(word: &str) -> String {
+    if is_token(word) {
+        return word.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in word.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Parse a media type such as `text/html; charset=utf-8` into the lowercased
+/// `type/subtype` and parameters.
+fn parse_media_type(s: &str) -> Option<(String, Vec<(String, String)>)> {
+    let mut parts = split_unquoted(s, ';').into_iter();
+    let mime_type = parts.next()?.trim();
+    let (type_, subtype) = mime_type.split_once('/')?;
+    if !is_token(type_) || !is_token(subtype) {
+        return None;
+    }
+    let params = parts
+        .map(|param| {
+            let (name, value) = param.trim().split_once('=')?;
+            if !is_token(name) {
+                return None;
+            }
+            Some((name.to_ascii_lowercase(), parse_word(value)?))
+        })
+        .collect::<Option<Vec<(String, String)>>>()?;
+    Some((mime_type.to_ascii_lowercase(), params))
+}
+
+fn format_media_type(mime_type: &str, params: &[(String, String)]) -> String {
+    let mut media_type = mime_type.to_string();
+    for (name, value) in params {
+        media_type.push_str(&format!("; {}={}", name, format_word(value)));
+    }
+    media_type
+}
+
+/// `Content-Type` header: a media type with parameters.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ContentType {
+    /// Lowercased `type/subtype` such as `text/html`.
+    pub mime_type: String,
+    /// Parameters with lowercased names.
+    pub params: Vec<(String, String)>,
+}
+
+impl ContentType {
+    pub fn new(mime_type: &str) -> Self {
+        Self {
+            mime_type: mime_type.to_ascii_lowercase(),
+            params: Vec::new(),
+        }
+    }
+
+    pub fn param(self, name: &str, value: &str) -> Self {
+        let mut params = self.params;
+        params.push((name.to_ascii_lowercase(), value.to_string()));
+        Self { params, ..self }
+    }
+
+    pub fn get_param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn charset(&self) -> Option<&str> {
+        self.get_param("charset")
+    }
+}
+
+impl TypedHeader for ContentType {
+    const NAME: HeaderName = HeaderName::CONTENT_TYPE;
+
+    fn decode(values: &[&str]) -> Result<Self, TypedHeaderError> {
+        let (mime_type, params) =
+            parse_media_type(single::<Self>(values)?).ok_or_else(invalid::<Self>)?;
+        Ok(Self { mime_type, params })
+    }
+
+    fn encode(&self) -> String {
+        format_media_type(&self.mime_type, &self.params)
+    }
+}
+
+/// `Content-Length` header.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ContentLength(pub u64);
+
+impl TypedHeader for ContentLength {
+    const NAME: HeaderName = HeaderName::CONTENT_LENGTH;
+
+    /// Repeated values are accepted only if all of them are the same.
+    fn decode(values: &[&str]) -> Result<Self, TypedHeaderError> {
+        let mut lengths = values
+            .iter()
+            .flat_map(|value| value.split(','))
+            .map(|value| {
+                let value = value.trim();
+                if value.is_empty() || !value.bytes().all(|c| c.is_ascii_digit()) {
+                    return Err(invalid::<Self>());
+                }
+                value.parse::<u64>().map_err(|_| invalid::<Self>())
+            });
+        let length = lengths.next().ok_or_else(invalid::<Self>)??;
+        for other in lengths {
+            if other? != length {
+                return Err(invalid::<Self>());
+            }
+        }
+        Ok(ContentLength(length))
+    }
+
+    fn encode(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+/// Media range in `Accept` header with its quality value.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MediaRange {
+    /// Lowercased `type/subtype`, which can be `type/*` or `*/*`.
+    pub mime_type: String,
+    /// Parameters other than `q`.
+    pub params: Vec<(String, String)>,
+    /// Quality value in thousandths, from 0 to 1000.
+    pub quality: u16,
+}
+
+impl MediaRange {
+    pub fn new(mime_type: &str, quality: u16) -> Self {
+        Self {
+            mime_type: mime_type.to_ascii_lowercase(),
+            params: Vec::new(),
+            quality: quality.min(1000),
+        }
+    }
+
+    /// Check if the media range includes the media type such as `text/html`.
+    pub fn matches(&self, mime_type: &str) -> bool {
+        match self.mime_type.split_once('/') {
+            Some(("*", "*")) => true,
+            Some((range_type, "*")) => mime_type
+                .split_once('/')
+                .is_some_and(|(type_, _)| range_type.eq_ignore_ascii_case(type_)),
+            _ => self.mime_type.eq_ignore_ascii_case(mime_type),
+        }
+    }
+}
+
+/// Parse a quality value such as `0.8` into thousandths.
+fn parse_quality(s: &str) -> Option<u16> {
+    let (integer, fraction) = s.split_once('.').unwrap_or((s, ""));
+    if fraction.len() > 3 || !fraction.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let fraction = format!("{:0<3}", fraction).parse::<u16>().ok()?;
+    match integer {
+        "0" => Some(fraction),
+        "1" if fraction == 0 => Some(1000),
+        _ => None,
+    }
+}
+
+fn format_quality(quality: u16) -> String {
+    if quality >= 1000 {
+        return "1".to_string();
+    }
+    let fraction = format!("{:03}", quality);
+    format!("0.{}", fraction.trim_end_matches('0'))
+        .trim_end_matches('.')
+        .to_string()
+}
+
+/// `Accept` header: media ranges acceptable for the client.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Accept(pub Vec<MediaRange>);
+
+impl Accept {
+    /// Return the quality of the media type by the most specific matching range,
+    /// or 0 if no range matches.
+    pub fn quality(&self, mime_type: &str) -> u16 {
+        self.0
+            .iter()
+            .filter(|range| range.matches(mime_type))
+            .min_by_key(|range| range.mime_type.matches('*').count())
+            .map(|range| range.quality)
+            .unwrap_or(0)
+    }
+
+    /// Return the media ranges sorted by quality in descending order.
+    pub fn preferred(&self) -> Vec<&MediaRange> {
+        let mut ranges = self.0.iter().collect::<Vec<&MediaRange>>();
+        ranges.sort_by_key(|range| std::cmp::Reverse(range.quality));
+        ranges
+    }
+}
+
+impl TypedHeader for Accept {
+    const NAME: HeaderName = HeaderName::ACCEPT;
+
+    fn decode(values: &[&str]) -> Result<Self, TypedHeaderError> {
+        let mut ranges = Vec::new();
+        for value in values {
+            for range in split_unquoted(value, ',') {
+                if range.trim().is_empty() {
+                    continue;
+                }
+                let (mime_type, params) = parse_media_type(range).ok_or_else(invalid::<Self>)?;
+                let mut quality = 1000;
+                let mut other_params = Vec::new();
+                for (name, value) in params {
+                    if name == "q" {
+                        quality = parse_quality(&value).ok_or_else(invalid::<Self>)?;
+                    } else {
+                        other_params.push((name, value));
+                    }
+                }
+                ranges.push(MediaRange {
+                    mime_type,
+                    params: other_params,
+                    quality,
+                });
+            }
+        }
+        Ok(Accept(ranges))
+    }
+
+    fn encode(&self) -> String {
+        self.0
+            .iter()
+            .map(|range| {
+                let media_type = format_media_type(&range.mime_type, &range.params);
+                if range.quality >= 1000 {
+                    media_type
+                } else {
+                    format!("{}; q={}", media_type, format_quality(range.quality))
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+/// `Cache-Control` header: directives with optional arguments.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CacheControl(pub Vec<(String, Option<String>)>);
+
+impl CacheControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a directive such as `no-cache` or `max-age=60`.
+    pub fn directive(self, name: &str, argument: Option<&str>) -> Self {
+        let mut directives = self.0;
+        directives.push((name.to_ascii_lowercase(), argument.map(str::to_string)));
+        CacheControl(directives)
+    }
+
+    pub fn has(&self, name: &str) -> bool {
+        self.0.iter().any(|(key, _)| key.eq_ignore_ascii_case(name))
+    }
+
+    /// Return the argument of the directive.
+    pub fn argument(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .and_then(|(_, argument)| argument.as_deref())
+    }
+
+    pub fn max_age(&self) -> Option<u64> {
+        self.argument("max-age")?.parse().ok()
+    }
+
+    pub fn no_cache(&self) -> bool {
+        self.has("no-cache")
+    }
+
+    pub fn no_store(&self) -> bool {
+        self.has("no-store")
+    }
+}
+
+impl TypedHeader for CacheControl {
+    const NAME: HeaderName = HeaderName::CACHE_CONTROL;
+
+    fn decode(values: &[&str]) -> Result<Self, TypedHeaderError> {
+        let mut directives = Vec::new();
+        for value in values {
+            for directive in split_unquoted(value, ',') {
+                let directive = directive.trim();
+                if directive.is_empty() {
+                    continue;
+                }
+                let (name, argument) = match directive.split_once('=') {
+                    Some((name, argument)) => (
+                        name,
+                        Some(parse_word(argument).ok_or_else(invalid::<Self>)?),
+                    ),
+                    None => (directive, None),
+                };
+                if !is_token(name) {
+                    return Err(invalid::<Self>());
+                }
+                directives.push((name.to_ascii_lowercase(), argument));
+            }
+        }
+        Ok(CacheControl(directives))
+    }
+
+    fn encode(&self) -> String {
+        self.0
+            .iter()
+            .map(|(name, argument)| match argument {
+                Some(argument) => format!("{}={}", name, format_word(argument)),
+                None => name.clone(),
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+/// `Authorization` header: an authentication scheme and credentials.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Authorization {
+    pub scheme: String,
+    /// Credentials following the scheme, such as a token of `Bearer` scheme.
+    pub credentials: String,
+}
+
+impl Authorization {
+    pub fn bearer(token: &str) -> Self {
+        Self {
+            scheme: "Bearer".to_string(),
+            credentials: token.to_string(),
+        }
+    }
+
+    /// Check the scheme, which is case-insensitive.
+    pub fn is_scheme(&self, scheme: &str) -> bool {
+        self.scheme.eq_ignore_ascii_case(scheme)
+    }
+}
+
+impl TypedHeader for Authorization {
+    const NAME: HeaderName = HeaderName::AUTHORIZATION;
+
+    fn decode(values: &[&str]) -> Result<Self, TypedHeaderError> {
+        let value = single::<Self>(values)?;
+        let (scheme, credentials) = value.split_once(' ').unwrap_or((value, ""));
+        if !is_token(scheme) {
+            return Err(invalid::<Self>());
+        }
+        Ok(Self {
+            scheme: scheme.to_string(),
+            credentials: credentials.trim().to_string(),
+        })
+    }
+
+    fn encode(&self) -> String {
+        if self.credentials.is_empty() {
+            self.scheme.clone()
+        } else {
+            format!("{} {}", self.scheme, self.credentials)
+        }
+    }
+}
+
+/// `Date` header.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Date(pub SystemTime);
+
+impl TypedHeader for Date {
+    const NAME: HeaderName = HeaderName::DATE;
+
+    fn decode(values: &[&str]) -> Result<Self, TypedHeaderError> {
+        let date = http_date::parse(single::<Self>(values)?).ok_or_else(invalid::<Self>)?;
+        Ok(Date(date))
+    }
+
+    /// Fractional seconds are dropped.
+    fn encode(&self) -> String {
+        http_date::format(self.0)
+    }
+}
+
+/// `Host` header: a host name or IP address with an optional port.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Host {
+    /// Host name, IPv4 address or IPv6 address in brackets such as `[::1]`.
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl TypedHeader for Host {
+    const NAME: HeaderName = HeaderName::HOST;
+
+    fn decode(values: &[&str]) -> Result<Self, TypedHeaderError> {
+        let value = single::<Self>(values)?;
+        // Colons in an IPv6 address are not a port separator.
+        let port_separator = match value.rfind(']') {
+            Some(end) => value[end..].find(':').map(|pos| end + pos),
+            None => value.rfind(':'),
+        };
+        let (host, port) = match port_separator {
+            Some(pos) => {
+                let port = value[pos + 1..].parse().map_err(|_| invalid::<Self>())?;
+                (&value[..pos], Some(port))
+            }
+            None => (value, None),
+        };
+        let is_valid = |c: char| c.is_ascii_alphanumeric() || "-._~%!$&'()*+,;=:[]".contains(c);
+        if host.is_empty() || !host.chars().all(is_valid) {
+            return Err(invalid::<Self>());
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+        })
+    }
+
+    fn encode(&self) -> String {
+        match self.port {
+            Some(port) => format!("{}:{}", self.host, port),
+            None => self.host.clone(),
+        }
+    }
+}
+
+/// `Range` header: byte ranges which are not resolved against a representation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Range(pub Vec<RangeSpec>);
+
+impl TypedHeader for Range {
+    const NAME: HeaderName = HeaderName::RANGE;
+
+    fn decode(values: &[&str]) -> Result<Self, TypedHeaderError> {
+        let specs = range::parse_specs(single::<Self>(values)?).map_err(|_| invalid::<Self>())?;
+        Ok(Range(specs))
+    }
+
+    fn encode(&self) -> String {
+        let specs = self
+            .0
+            .iter()
+            .map(|spec| spec.to_string())
+            .collect::<Vec<String>>();
+        format!("bytes={}", specs.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::headers::{HeaderMap, HeaderName};
+    use crate::range::RangeSpec;
+    use crate::request::Request;
+    use crate::typed_headers::{
+        Accept, Authorization, CacheControl, ContentLength, ContentType, Date, Host, MediaRange,
+        Range, TypedHeader, TypedHeaderError,
+    };
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_typed_header() {
+        let request = Request::new(
+            b"POST / HTTP/1.1\r\nContent-Type: Text/HTML; Charset=\"utf-8\"\r\nContent-Length: 0\r\n\r\n",
+        )
+        .unwrap();
+        let content_type = request.typed_header::<ContentType>().unwrap();
+        assert_eq!(content_type.mime_type, "text/html");
+        assert_eq!(content_type.charset(), Some("utf-8"));
+        assert_eq!(
+            request.typed_header::<ContentLength>(),
+            Ok(ContentLength(0))
+        );
+        assert_eq!(
+            request.typed_header::<Host>(),
+            Err(TypedHeaderError::Missing(HeaderName::HOST))
+        );
+    }
+
+    #[test]
+    fn test_content_type() {
+        let content_type = ContentType::decode(&["multipart/form-data; boundary=\"a;b\""]).unwrap();
+        assert_eq!(content_type.mime_type, "multipart/form-data");
+        assert_eq!(content_type.get_param("Boundary"), Some("a;b"));
+        assert_eq!(
+            content_type.encode(),
+            "multipart/form-data; boundary=\"a;b\""
+        );
+        for value in &[
+            "text",
+            "text/",
+            "text/html; charset",
+            "text/html; charset=\"a",
+            "a b/c",
+        ] {
+            assert_eq!(
+                ContentType::decode(&[value]),
+                Err(TypedHeaderError::Invalid(HeaderName::CONTENT_TYPE)),
+                "{}",
+                value
+            );
+        }
+        assert!(ContentType::decode(&["text/html", "text/plain"]).is_err());
+    }
+
+    #[test]
+    fn test_content_length() {
+        assert_eq!(ContentLength::decode(&["42"]), Ok(ContentLength(42)));
+        assert_eq!(
+            ContentLength::decode(&["42", "42, 42"]),
+            Ok(ContentLength(42))
+        );
+        assert!(ContentLength::decode(&["42", "43"]).is_err());
+        assert!(ContentLength::decode(&["-1"]).is_err());
+        assert!(ContentLength::decode(&["+1"]).is_err());
+        assert!(ContentLength::decode(&[""]).is_err());
+    }
+
+    #[test]
+    fn test_accept() {
+        let accept = Accept::decode(&[
+            "text/html, application/xhtml+xml, application/xml;q=0.9",
+            "*/*;q=0.8, text/*;q=0",
+        ])
+        .unwrap();
+        assert_eq!(accept.0.len(), 5);
+        assert_eq!(accept.quality("text/html"), 1000);
+        assert_eq!(accept.quality("application/xml"), 900);
+        assert_eq!(accept.quality("image/png"), 800);
+        // The most specific range is used.
+        assert_eq!(accept.quality("text/plain"), 0);
+        let preferred = accept
+            .preferred()
+            .iter()
+            .map(|range| range.mime_type.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            preferred,
+            vec![
+                "text/html",
+                "application/xhtml+xml",
+                "application/xml",
+                "*/*",
+                "text/*"
+            ]
+        );
+        assert_eq!(
+            Accept(vec![
+                MediaRange::new("text/*", 250),
+                MediaRange::new("*/*", 0)
+            ])
+            .encode(),
+            "text/*; q=0.25, */*; q=0"
+        );
+        for value in &[
+            "text/html;q=2",
+            "text/html;q=0.1234",
+            "text/html;q=1.5",
+            "text",
+        ] {
+            assert!(Accept::decode(&[value]).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_cache_control() {
+        let cache_control =
+            CacheControl::decode(&["no-cache, max-age=60", "private=\"Set-Cookie, Vary\""])
+                .unwrap();
+        assert!(cache_control.no_cache());
+        assert!(!cache_control.no_store());
+        assert_eq!(cache_control.max_age(), Some(60));
+        assert_eq!(cache_control.argument("private"), Some("Set-Cookie, Vary"));
+        assert_eq!(
+            cache_control.encode(),
+            "no-cache, max-age=60, private=\"Set-Cookie, Vary\""
+        );
+        assert!(CacheControl::decode(&["max age=1"]).is_err());
+    }
+
+    #[test]
+    fn test_authorization() {
+        let authorization = Authorization::decode(&["basic dXNlcjpwYXNz"]).unwrap();
+        assert!(authorization.is_scheme("Basic"));
+        assert_eq!(authorization.credentials, "dXNlcjpwYXNz");
+        assert_eq!(authorization.encode(), "basic dXNlcjpwYXNz");
+        assert!(Authorization::decode(&["a@b token"]).is_err());
+        assert!(Authorization::decode(&["Bearer a", "Bearer b"]).is_err());
+    }
+
+    #[test]
+    fn test_date() {
+        let date = Date(UNIX_EPOCH + Duration::from_secs(784_111_777));
+        assert_eq!(date.encode(), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(Date::decode(&["Sunday, 06-Nov-94 08:49:37 GMT"]), Ok(date));
+        assert!(Date::decode(&["yesterday"]).is_err());
+    }
+
+    #[test]
+    fn test_host() {
+        let host = |host: &str, port| Host {
+            host: host.to_string(),
+            port,
+        };
+        assert_eq!(
+            Host::decode(&["example.com"]),
+            Ok(host("example.com", None))
+        );
+        assert_eq!(
+            Host::decode(&["localhost:8000"]),
+            Ok(host("localhost", Some(8000)))
+        );
+        assert_eq!(Host::decode(&["[::1]"]), Ok(host("[::1]", None)));
+        assert_eq!(Host::decode(&["[::1]:80"]), Ok(host("[::1]", Some(80))));
+        for value in &["", ":80", "a:b", "a:99999", "a b", "a/b"] {
+            assert!(Host::decode(&[value]).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_range() {
+        let range = Range::decode(&["bytes=0-0, -1"]).unwrap();
+        assert_eq!(range.0, vec![RangeSpec::FromTo(0, 0), RangeSpec::Suffix(1)]);
+        assert_eq!(range.encode(), "bytes=0-0,-1");
+        assert!(Range::decode(&["items=0-1"]).is_err());
+    }
+
+    #[test]
+    fn test_typed_insert_replaces_values() {
+        let mut headers = HeaderMap::new();
+        headers.append(HeaderName::CONTENT_LENGTH, "1");
+        headers.append(HeaderName::CONTENT_LENGTH, "2");
+        headers.typed_insert(&ContentLength(3)).unwrap();
+        assert_eq!(
+            headers
+                .get_all(&HeaderName::CONTENT_LENGTH)
+                .collect::<Vec<&str>>(),
+            vec!["3"]
+        );
+    }
+
+    #[test]
+    fn test_typed_insert_rejects_line_breaks() {
+        let mut headers = HeaderMap::new();
+        let authorization = Authorization::bearer("x\r\nSet-Cookie: a=b");
+        assert_eq!(
+            headers.typed_insert(&authorization),
+            Err(TypedHeaderError::Invalid(HeaderName::AUTHORIZATION))
+        );
+        let content_type = ContentType::new("text/plain").param("charset", "utf-8\r\nX: y");
+        assert_eq!(
+            headers.typed_insert(&content_type),
+            Err(TypedHeaderError::Invalid(HeaderName::CONTENT_TYPE))
+        );
+        let host = Host {
+            host: "a\0b".to_string(),
+            port: None,
+        };
+        assert!(headers.typed_insert(&host).is_err());
+        assert!(headers.is_empty());
+    }
+}
@@ -0,0 +1,130 @@
+/// Decode percent-encoded octets such as `%2F` in the string.
+/// Return `None` if an escape is malformed or decoded octets are not valid UTF-8.
+pub fn percent_decode(input: &str) -> Option<String> {
+    let input = input.as_bytes();
+    let mut decoded = Vec::with_capacity(input.len());
+    let mut pos = 0;
+    while pos < input.len() {
+        if input[pos] == b'%' {
+            let high = input.get(pos + 1).and_then(|c| (*c as char).to_digit(16))?;
+            let low = input.get(pos + 2).and_then(|c| (*c as char).to_digit(16))?;
+            decoded.push((high * 16 + low) as u8);
+            pos += 3;
+        } else {
+            decoded.push(input[pos]);
+            pos += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Parse a query string such as `page=2&tag=a&tag=b` into pairs of names and values in
+/// order. `+` is decoded into a space as in HTML forms, and a pair without `=` has an empty
+/// value. Return `None` if an escape is malformed.
+pub fn parse_query(query: &str) -> Option<Vec<(String, String)>> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let decode = |s: &str| percent_decode(&s.replace('+', " "));
+            Some((decode(name)?, decode(value)?))
+        })
+        .collect()
+}
+
+/// Format pairs of names and values into a query string, which `parse_query` parses back
+/// into the same pairs.
+pub fn format_query(pairs: &[(String, String)]) -> String {
+    pairs
+        .iter()
+        .map(|(name, value)| format!("{}={}", percent_encode(name), percent_encode(value)))
+        .collect::<Vec<String>>()
+        .join("&")
+}
+
+/// Percent-encode the string so that it can be used as a segment of a path.
+/// All characters other than unreserved ones defined in RFC 3986 are encoded.
+pub fn percent_encode(input: &str) -> String {
+    let mut encoded = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::uri::{format_query, parse_query, percent_decode, percent_encode};
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(
+            percent_decode("/index.html"),
+            Some("/index.html".to_string())
+        );
+        assert_eq!(percent_decode("a%20b%2Fc%2f"), Some("a b/c/".to_string()));
+        assert_eq!(percent_decode("%E3%81%82"), Some("あ".to_string()));
+        assert_eq!(percent_decode("%252e"), Some("%2e".to_string()));
+    }
+
+    #[test]
+    fn test_percent_decode_malformed() {
+        assert_eq!(percent_decode("%"), None);
+        assert_eq!(percent_decode("%2"), None);
+        assert_eq!(percent_decode("%zz"), None);
+        assert_eq!(percent_decode("%+1"), None);
+        // Overlong encoding of '.' is not valid UTF-8.
+        assert_eq!(percent_decode("%c0%ae"), None);
+    }
+
+    #[test]
+    fn test_percent_encode() {
+        assert_eq!(percent_encode("index.html"), "index.html");
+        assert_eq!(percent_encode("a b/c?#%"), "a%20b%2Fc%3F%23%25");
+        assert_eq!(percent_encode("あ"), "%E3%81%82");
+        assert_eq!(
+            percent_decode(&percent_encode("a b&<>")),
+            Some("a b&<>".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let pairs = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<Vec<(String, String)>>()
+        };
+        assert_eq!(
+            parse_query("page=2&tag=a&tag=b"),
+            Some(pairs(&[("page", "2"), ("tag", "a"), ("tag", "b")]))
+        );
+        assert_eq!(
+            parse_query("q=a+b%2Bc&flag&&empty=&x=%3D%26"),
+            Some(pairs(&[
+                ("q", "a b+c"),
+                ("flag", ""),
+                ("empty", ""),
+                ("x", "=&")
+            ]))
+        );
+        assert_eq!(parse_query(""), Some(Vec::new()));
+        assert_eq!(parse_query("a=%zz"), None);
+        assert_eq!(parse_query("%=1"), None);
+    }
+
+    #[test]
+    fn test_format_query() {
+        let query = parse_query("q=a+b%2Bc&flag&x=%3D%26").unwrap();
+        assert_eq!(format_query(&query), "q=a%20b%2Bc&flag=&x=%3D%26");
+        assert_eq!(parse_query(&format_query(&query)), Some(query));
+        assert_eq!(format_query(&[]), "");
+    }
+}